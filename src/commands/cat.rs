@@ -1,31 +1,50 @@
+use crate::interrupt;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+use std::io::{self, Read, Write};
 
-pub fn cat(args: Vec<String>) {
+pub fn cat(
+    args: Vec<String>,
+    input: &mut dyn Read,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> io::Result<i32> {
+    // Without operands, copy standard input (e.g. at the end of a pipeline)
     if args.is_empty() {
-        println!("cat: missing file operand");
-        return;
+        io::copy(input, out)?;
+        return Ok(0);
     }
 
+    let mut status = 0;
     for filename in args {
-        if let Err(err) = print_file(&filename) {
-            println!("cat: {}: {}", filename, err);
+        if filename == "-" {
+            io::copy(input, out)?;
+            continue;
         }
-    }
-}
-
-fn print_file(filename: &str) -> io::Result<()> {
-    // Open the file
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-
-    // Read and print each line
-    for line in reader.lines() {
-        match line {
-            Ok(content) => println!("{}", content),
-            Err(err) => return Err(err),
+        let mut file = match File::open(&filename) {
+            Ok(file) => file,
+            Err(e) => {
+                writeln!(err, "cat: {}: {}", filename, e)?;
+                status = 1;
+                continue;
+            }
+        };
+        // Streamed in chunks, so that large or endless files are never held
+        // in memory
+        let mut buffer = [0; 8192];
+        loop {
+            interrupt::check()?;
+            match file.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(count) => out.write_all(&buffer[..count])?,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    writeln!(err, "cat: {}: {}", filename, e)?;
+                    status = 1;
+                    break;
+                }
+            }
         }
     }
 
-    Ok(())
+    Ok(status)
 }
@@ -1,11 +1,14 @@
 use std::env;
+use std::io::{self, Write};
 use std::path::Path;
 
 // Main function of cd
-pub fn cd(args: Vec<String>) {
-    if let Err(err) = handle_cd(args) {
-        println!("{}", err);
+pub fn cd(args: Vec<String>, err: &mut dyn Write) -> io::Result<i32> {
+    if let Err(e) = handle_cd(args) {
+        writeln!(err, "{}", e)?;
+        return Ok(1);
     }
+    Ok(0)
 }
 
 fn handle_cd(args: Vec<String>) -> Result<(), String> {
@@ -37,8 +40,17 @@ fn handle_cd(args: Vec<String>) -> Result<(), String> {
     };
 
     // Change directory
+    let previous = env::current_dir().ok();
     let path = Path::new(&new_dir);
-    env::set_current_dir(&path).map_err(|e| format!("cd: {}: {}", new_dir, e))?;
+    env::set_current_dir(path).map_err(|e| format!("cd: {}: {}", new_dir, e))?;
+
+    // Keep $PWD and $OLDPWD in sync for expansions and child processes
+    if let Some(previous) = previous {
+        env::set_var("OLDPWD", previous);
+    }
+    if let Ok(current) = env::current_dir() {
+        env::set_var("PWD", current);
+    }
 
     Ok(())
 }
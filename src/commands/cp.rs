@@ -1,37 +1,182 @@
-use std::fs;
-use std::path::Path;
-use std::io;
+use super::options::{may_replace, parse_flags, split_operands, FlagSpec, Overwrite};
+use crate::interrupt;
+use std::ffi::CString;
+use std::fs::{self, DirBuilder, File, Metadata, OpenOptions, Permissions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{
+    symlink, DirBuilderExt, FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt,
+};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::ptr;
 
-pub fn cp(args: Vec<String>) {
-    if args.len() != 2 {
-        println!("cp: missing file operand");
-        println!("Usage: cp <source> <destination>");
-        return;
+// Flags accepted on the command line, also offered by tab completion
+pub const SPEC: FlagSpec = FlagSpec {
+    short: "rRinfupa",
+    long: &[
+        ("recursive", 'r'),
+        ("interactive", 'i'),
+        ("no-clobber", 'n'),
+        ("force", 'f'),
+        ("update", 'u'),
+        ("preserve", 'p'),
+        ("archive", 'a'),
+    ],
+    with_value: &['p'],
+};
+
+// How to copy, from the flags
+#[derive(Default)]
+struct Options {
+    recursive: bool,
+    overwrite: Overwrite,
+    // Replace destinations that cannot be opened (`-f`)
+    force: bool,
+    // Only replace older files (`-u`)
+    update: bool,
+    preserve: Preserve,
+}
+
+// Attributes carried over from the sources (`-p`, `--preserve`)
+#[derive(Default)]
+struct Preserve {
+    mode: bool,
+    ownership: bool,
+    timestamps: bool,
+    xattr: bool,
+    // Extended attributes were asked for by name, not through -a or `all`,
+    // so that failing to copy any of them is an error
+    xattr_required: bool,
+}
+
+impl Preserve {
+    // What `-p` and a bare `--preserve` keep
+    fn basic() -> Preserve {
+        Preserve {
+            mode: true,
+            ownership: true,
+            timestamps: true,
+            ..Preserve::default()
+        }
+    }
+
+    fn all() -> Preserve {
+        Preserve {
+            xattr: true,
+            ..Preserve::basic()
+        }
+    }
+
+    // Parse the list of `--preserve=mode,ownership,...`
+    fn parse(list: &str) -> Result<Preserve, String> {
+        let mut preserve = Preserve::default();
+        for attribute in list.split(',') {
+            match attribute {
+                "mode" => preserve.mode = true,
+                "ownership" => preserve.ownership = true,
+                "timestamps" => preserve.timestamps = true,
+                "xattr" => {
+                    preserve.xattr = true;
+                    preserve.xattr_required = true;
+                }
+                "all" => preserve.add(Preserve::all()),
+                _ => return Err(format!("invalid argument '{}' for '--preserve'", attribute)),
+            }
+        }
+        Ok(preserve)
+    }
+
+    // Keep the attributes of `other` as well, so that `-a -p` or
+    // `-p --preserve=xattr` add up
+    fn add(&mut self, other: Preserve) {
+        self.mode |= other.mode;
+        self.ownership |= other.ownership;
+        self.timestamps |= other.timestamps;
+        self.xattr |= other.xattr;
+        self.xattr_required |= other.xattr_required;
     }
-    let source = &args[0];
-    let destination = &args[1];
-    if let Err(err) = copy(&source, &destination) {
-        println!("cp: {}", err);
+}
+
+pub fn cp(args: Vec<String>, err: &mut dyn Write) -> io::Result<i32> {
+    let mut options = Options::default();
+    let operands = parse_flags("cp", args, &SPEC, err, |flag, value| {
+        match flag {
+            'r' | 'R' => options.recursive = true,
+            'i' => options.overwrite = Overwrite::Ask,
+            'n' => options.overwrite = Overwrite::Never,
+            'f' => {
+                options.overwrite = Overwrite::Always;
+                options.force = true;
+            }
+            'u' => options.update = true,
+            'p' => options.preserve.add(match value {
+                Some(list) => Preserve::parse(list)?,
+                None => Preserve::basic(),
+            }),
+            // Links are copied as links whenever copying recursively
+            'a' => {
+                options.recursive = true;
+                options.preserve.add(Preserve::all());
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    let operands = match operands {
+        Some(operands) => operands,
+        None => return Ok(1),
+    };
+
+    let usage = "cp [-rfinupa] <source>... <destination>";
+    let (sources, destination) = match split_operands("cp", usage, &operands, err)? {
+        Some(split) => split,
+        None => return Ok(1),
+    };
+
+    let mut status = 0;
+    for source in sources {
+        interrupt::check()?;
+        let mut errors = Vec::new();
+        if let Err(e) = copy(source, destination, &options, &mut errors) {
+            errors.push(e);
+        }
+        for e in &errors {
+            writeln!(err, "cp: {}", e)?;
+            status = 1;
+        }
     }
+    Ok(status)
 }
 
-fn copy(source: &str, destination: &str) -> io::Result<()> {
+fn copy(
+    source: &str,
+    destination: &str,
+    options: &Options,
+    errors: &mut Vec<io::Error>,
+) -> io::Result<()> {
     let source_path = Path::new(source);
     let destination_path = Path::new(destination);
 
-    // Check if the source exist
-    if !source_path.exists() {
-        return Err(io::Error::new(
+    // Check if the source exist. With -r a symbolic link is copied as a
+    // link, otherwise the file it points to is copied.
+    let metadata = if options.recursive {
+        fs::symlink_metadata(source_path)
+    } else {
+        fs::metadata(source_path)
+    };
+    let metadata = metadata.map_err(|_| {
+        io::Error::new(
             io::ErrorKind::NotFound,
-            format!("cannot stat '{}': No such file or directory", source)
-        ));
-    }
+            format!("cannot stat '{}': No such file or directory", source),
+        )
+    })?;
 
     // Check if the source is a directory
-    if source_path.is_dir() {
+    if metadata.is_dir() && !options.recursive {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
-            format!("'{}' is a directory (not copied)", source)
+            format!("-r not specified; omitting directory '{}'", source),
         ));
     }
 
@@ -44,14 +189,403 @@ fn copy(source: &str, destination: &str) -> io::Result<()> {
         destination_path.to_path_buf()
     };
 
-    // Check if the destination path exist
-    if !final_destination.parent().map_or(true, |p| p.exists()) {
+    // Check if the destination path exist (a bare name is in the current
+    // directory)
+    let parent_exists = final_destination
+        .parent()
+        .is_none_or(|p| p.as_os_str().is_empty() || p.exists());
+    if !parent_exists {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
-            format!("cannot found '{}': No such directory", destination)
+            format!("cannot found '{}': No such directory", destination),
         ));
     }
 
-    // Copy the file
-    fs::copy(source_path, &final_destination).map(|_| ())
-}
\ No newline at end of file
+    if let Ok(existing) = fs::metadata(&final_destination) {
+        if existing.dev() == metadata.dev() && existing.ino() == metadata.ino() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "'{}' and '{}' are the same file",
+                    source,
+                    final_destination.display()
+                ),
+            ));
+        }
+        if metadata.is_dir() && !existing.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "cannot overwrite non-directory '{}' with directory '{}'",
+                    final_destination.display(),
+                    source
+                ),
+            ));
+        }
+    }
+
+    if metadata.is_dir() {
+        if is_inside(&final_destination, source_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "cannot copy a directory, '{}', into itself, '{}'",
+                    source,
+                    final_destination.display()
+                ),
+            ));
+        }
+        copy_tree(source_path, &final_destination, &metadata, options, errors);
+        return Ok(());
+    }
+
+    copy_entry(source_path, &final_destination, &metadata, options)
+}
+
+// Whether `path`, which may not exist yet, is `directory` or somewhere below it
+fn is_inside(path: &Path, directory: &Path) -> bool {
+    let directory = match fs::canonicalize(directory) {
+        Ok(directory) => directory,
+        Err(_) => return false,
+    };
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => {
+            let parent = if parent.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                parent
+            };
+            fs::canonicalize(parent).map(|parent| parent.join(name))
+        }
+        _ => fs::canonicalize(path),
+    };
+    resolved.is_ok_and(|path| path.starts_with(&directory))
+}
+
+// Recreate a directory and everything below it. Errors are collected so that
+// one unreadable file does not stop the rest of the copy.
+fn copy_tree(
+    source: &Path,
+    destination: &Path,
+    metadata: &Metadata,
+    options: &Options,
+    errors: &mut Vec<io::Error>,
+) {
+    // A new directory stays writable until its entries are copied, so that
+    // read-only directories can be copied too
+    let created = !destination.is_dir();
+    if created {
+        if let Err(e) = DirBuilder::new().mode(0o700).create(destination) {
+            errors.push(describe(e, "cannot create directory", destination));
+            return;
+        }
+    }
+
+    copy_entries(source, destination, options, errors);
+
+    // Only now, as adding the entries changed the times of the directory
+    let result = File::open(destination)
+        .map_err(|e| describe(e, "cannot access", destination))
+        .and_then(|directory| {
+            set_attributes(
+                source,
+                destination,
+                Some(&directory),
+                metadata,
+                &options.preserve,
+                created,
+            )
+        });
+    if let Err(e) = result {
+        errors.push(e);
+    }
+}
+
+fn copy_entries(source: &Path, destination: &Path, options: &Options, errors: &mut Vec<io::Error>) {
+    let entries = match fs::read_dir(source) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(describe(e, "cannot access", source));
+            return;
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if interrupt::is_set() {
+            return;
+        }
+        let target = destination.join(path.file_name().unwrap_or_default());
+        let result = fs::symlink_metadata(&path).and_then(|metadata| {
+            if metadata.is_dir() {
+                copy_tree(&path, &target, &metadata, options, errors);
+                Ok(())
+            } else {
+                copy_entry(&path, &target, &metadata, options)
+            }
+        });
+        if let Err(e) = result {
+            errors.push(describe(e, "cannot copy", &path));
+        }
+    }
+}
+
+// The file mode creation mask of the shell, which can only be read by
+// setting it
+fn umask() -> u32 {
+    unsafe {
+        let mask = libc::umask(0);
+        libc::umask(mask);
+        mask as u32
+    }
+}
+
+// Copy anything that is not a directory: symbolic links stay links and
+// special files are recreated rather than read
+fn copy_entry(
+    source: &Path,
+    destination: &Path,
+    metadata: &Metadata,
+    options: &Options,
+) -> io::Result<()> {
+    if !may_replace(
+        "cp",
+        metadata,
+        destination,
+        options.overwrite,
+        options.update,
+    ) {
+        return Ok(());
+    }
+    let (file, created) = create_copy(source, destination, metadata, options)?;
+    set_attributes(
+        source,
+        destination,
+        file.as_ref(),
+        metadata,
+        &options.preserve,
+        created,
+    )
+}
+
+// Returns the open copy of a regular file, and whether the destination is a
+// new file rather than one written over
+fn create_copy(
+    source: &Path,
+    destination: &Path,
+    metadata: &Metadata,
+    options: &Options,
+) -> io::Result<(Option<File>, bool)> {
+    let file_type = metadata.file_type();
+    let special = file_type.is_fifo()
+        || file_type.is_socket()
+        || file_type.is_char_device()
+        || file_type.is_block_device();
+    // Links and special files cannot be written over, only replaced
+    let replaced = fs::symlink_metadata(destination).is_ok_and(|m| !m.is_dir());
+    if (file_type.is_symlink() || special) && replaced {
+        fs::remove_file(destination)?;
+    }
+
+    if file_type.is_symlink() {
+        let target = fs::read_link(source)?;
+        symlink(target, destination)?;
+        return Ok((None, true));
+    }
+    if special {
+        let path = CString::new(destination.as_os_str().as_bytes())?;
+        let created = unsafe {
+            libc::mknod(
+                path.as_ptr(),
+                metadata.mode() as libc::mode_t,
+                metadata.rdev() as libc::dev_t,
+            )
+        };
+        if created != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        return Ok((None, true));
+    }
+
+    // A new copy is only readable and writable by its owner until its
+    // attributes are set. With -f a destination that cannot be opened is
+    // removed and the copy tried again.
+    let open = || {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(destination)
+    };
+    let (mut file, created) = match open() {
+        Err(e) if options.force && replaced && e.kind() == io::ErrorKind::PermissionDenied => {
+            fs::remove_file(destination)?;
+            (open()?, true)
+        }
+        result => (result?, !replaced),
+    };
+    io::copy(&mut File::open(source)?, &mut file)?;
+    Ok((Some(file), created))
+}
+
+// Give the copy the owner, extended attributes, mode and times of its source,
+// as requested. `file` is the open copy of a regular file or directory, and
+// links and special files are changed by name. Ownership goes first since
+// changing it clears the setuid and setgid bits, extended attributes before
+// the mode may forbid writing them, and times last since the other changes
+// touch them.
+fn set_attributes(
+    source: &Path,
+    destination: &Path,
+    file: Option<&File>,
+    metadata: &Metadata,
+    preserve: &Preserve,
+    created: bool,
+) -> io::Result<()> {
+    let symlink = metadata.file_type().is_symlink();
+    let path = CString::new(destination.as_os_str().as_bytes())?;
+
+    if preserve.ownership {
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        let result = match file {
+            Some(file) => unsafe { libc::fchown(file.as_raw_fd(), uid, gid) },
+            None => unsafe { libc::lchown(path.as_ptr(), uid, gid) },
+        };
+        if result != 0 {
+            // Only root may give files away; like GNU cp, that is not an error
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(libc::EPERM) {
+                return Err(describe(e, "failed to preserve ownership for", destination));
+            }
+        }
+    }
+
+    if let (true, Some(file)) = (preserve.xattr, file) {
+        copy_xattrs(source, file, preserve.xattr_required)
+            .map_err(|e| describe(e, "preserving extended attributes for", destination))?;
+    }
+
+    // Without -p, new files and directories get the mode of the source less
+    // the umask. Symbolic links have no mode of their own.
+    let mode = if preserve.mode {
+        Some(metadata.mode() & 0o7777)
+    } else if created && file.is_some() {
+        Some(metadata.mode() & 0o777 & !umask())
+    } else {
+        None
+    };
+    if let Some(mode) = mode.filter(|_| !symlink) {
+        let permissions = Permissions::from_mode(mode);
+        match file {
+            Some(file) => file.set_permissions(permissions),
+            None => fs::set_permissions(destination, permissions),
+        }
+        .map_err(|e| describe(e, "preserving permissions for", destination))?;
+    }
+
+    if preserve.timestamps {
+        let times = [
+            libc::timespec {
+                tv_sec: metadata.atime() as libc::time_t,
+                tv_nsec: metadata.atime_nsec() as libc::c_long,
+            },
+            libc::timespec {
+                tv_sec: metadata.mtime() as libc::time_t,
+                tv_nsec: metadata.mtime_nsec() as libc::c_long,
+            },
+        ];
+        let result = match file {
+            Some(file) => unsafe { libc::futimens(file.as_raw_fd(), times.as_ptr()) },
+            None => unsafe {
+                libc::utimensat(
+                    libc::AT_FDCWD,
+                    path.as_ptr(),
+                    times.as_ptr(),
+                    libc::AT_SYMLINK_NOFOLLOW,
+                )
+            },
+        };
+        if result != 0 {
+            let e = io::Error::last_os_error();
+            return Err(describe(e, "preserving times for", destination));
+        }
+    }
+
+    Ok(())
+}
+
+// Copy every extended attribute the source has onto the open copy.
+// Filesystems without extended attributes are not an error, and unless
+// `required`, neither are the `security` and `trusted` attributes that only
+// privileged users may set.
+fn copy_xattrs(source: &Path, destination: &File, required: bool) -> io::Result<()> {
+    let source = CString::new(source.as_os_str().as_bytes())?;
+    let unsupported = |e: &io::Error| e.raw_os_error() == Some(libc::ENOTSUP);
+
+    // The names come as one buffer of NUL-terminated strings
+    let size = unsafe { libc::listxattr(source.as_ptr(), ptr::null_mut(), 0) };
+    if size < 0 {
+        let e = io::Error::last_os_error();
+        return if unsupported(&e) { Ok(()) } else { Err(e) };
+    }
+    let mut names = vec![0u8; size as usize];
+    let size = unsafe {
+        libc::listxattr(
+            source.as_ptr(),
+            names.as_mut_ptr() as *mut libc::c_char,
+            names.len(),
+        )
+    };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    names.truncate(size as usize);
+
+    for name in names
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+    {
+        let privileged = name.starts_with(b"security.") || name.starts_with(b"trusted.");
+        let name = CString::new(name)?;
+        let size = unsafe { libc::getxattr(source.as_ptr(), name.as_ptr(), ptr::null_mut(), 0) };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut value = vec![0u8; size as usize];
+        let size = unsafe {
+            libc::getxattr(
+                source.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr() as *mut libc::c_void,
+                value.len(),
+            )
+        };
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let result = unsafe {
+            libc::fsetxattr(
+                destination.as_raw_fd(),
+                name.as_ptr(),
+                value.as_ptr() as *const libc::c_void,
+                size as usize,
+                0,
+            )
+        };
+        if result != 0 {
+            let e = io::Error::last_os_error();
+            let denied = matches!(e.raw_os_error(), Some(libc::EPERM | libc::EACCES));
+            let skipped = denied && privileged && !required;
+            if !unsupported(&e) && !skipped {
+                return Err(e);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn describe(e: io::Error, action: &str, path: &Path) -> io::Error {
+    io::Error::new(e.kind(), format!("{} '{}': {}", action, path.display(), e))
+}
@@ -1,4 +1,9 @@
-pub fn echo(args: Vec<String>) {
+use std::io::{self, Write};
+
+// Options offered by tab completion
+pub const FLAGS: [&str; 1] = ["-n"];
+
+pub fn echo(args: Vec<String>, out: &mut dyn Write) -> io::Result<i32> {
     let mut skip_newline = false;
     let mut start_index = 0;
 
@@ -17,8 +22,9 @@ pub fn echo(args: Vec<String>) {
 
     // Print with or without newline
     if skip_newline {
-        print!("{}", output);
+        write!(out, "{}", output)?;
     } else {
-        println!("{}", output);
+        writeln!(out, "{}", output)?;
     }
+    Ok(0)
 }
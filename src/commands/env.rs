@@ -0,0 +1,19 @@
+use std::env;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+
+// Print the environment passed to the programs started by the shell
+pub fn env(args: Vec<String>, out: &mut dyn Write, err: &mut dyn Write) -> io::Result<i32> {
+    if let Some(arg) = args.first() {
+        writeln!(err, "env: '{}': unexpected operand", arg)?;
+        return Ok(1);
+    }
+
+    for (name, value) in env::vars_os() {
+        out.write_all(name.as_bytes())?;
+        out.write_all(b"=")?;
+        out.write_all(value.as_bytes())?;
+        out.write_all(b"\n")?;
+    }
+    Ok(0)
+}
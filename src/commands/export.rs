@@ -0,0 +1,44 @@
+use crate::expand::is_valid_name;
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+
+pub fn export(args: Vec<String>, out: &mut dyn Write, err: &mut dyn Write) -> io::Result<i32> {
+    // Without arguments, list the exported variables
+    if args.is_empty() {
+        // Names and values are written as raw bytes, as they need not be UTF-8
+        let mut vars: Vec<(OsString, OsString)> = env::vars_os().collect();
+        vars.sort();
+        for (name, value) in vars {
+            out.write_all(b"export ")?;
+            out.write_all(name.as_bytes())?;
+            out.write_all(b"=\"")?;
+            out.write_all(value.as_bytes())?;
+            out.write_all(b"\"\n")?;
+        }
+        return Ok(0);
+    }
+
+    let mut status = 0;
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+
+        if !is_valid_name(name) || value.is_some_and(|v| v.contains('\0')) {
+            writeln!(err, "export: `{}': not a valid identifier", arg)?;
+            status = 1;
+            continue;
+        }
+
+        // `export NAME` keeps the current value, or exports an empty one
+        match value {
+            Some(value) => env::set_var(name, value),
+            None if env::var_os(name).is_none() => env::set_var(name, ""),
+            None => {}
+        }
+    }
+    Ok(status)
+}
@@ -0,0 +1,32 @@
+use std::io::{self, Write};
+
+// List the history with its numbers, as used by `!n`
+pub fn history(
+    args: Vec<String>,
+    entries: &[String],
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> io::Result<i32> {
+    if args.len() > 1 {
+        writeln!(err, "history: too many arguments")?;
+        return Ok(1);
+    }
+
+    // `history N` only shows the last N lines
+    let count = match args.first() {
+        Some(arg) => match arg.parse::<usize>() {
+            Ok(count) => count,
+            Err(_) => {
+                writeln!(err, "history: {}: numeric argument required", arg)?;
+                return Ok(1);
+            }
+        },
+        None => entries.len(),
+    };
+
+    let start = entries.len().saturating_sub(count);
+    for (i, entry) in entries.iter().enumerate().skip(start) {
+        writeln!(out, "{:>5}  {}", i + 1, entry)?;
+    }
+    Ok(0)
+}
@@ -1,218 +1,1192 @@
+use crate::interrupt;
 use std::{
+    collections::HashMap,
+    env,
     ffi::CStr,
-    fs::{self, DirEntry, Metadata},
-    os::unix::fs::{MetadataExt, PermissionsExt},
-    time::{SystemTime, UNIX_EPOCH},
+    fs::{self, Metadata},
+    io::{self, Write},
+    mem,
+    os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-const PINK: &str = "\x1b[35m";
-const ORANGE: &str = "\x1b[33m";
-const CYAN: &str = "\x1b[36m";
-const RESET: &str = "\x1b[0m";
+// Colors used when `LS_COLORS` does not set them: pink directories,
+// orange executables and cyan symbolic links
+const DEFAULT_COLORS: &str = "di=35:ex=33:ln=36";
 
-pub fn ls(args: Vec<String>) {
-    let mut result = String::new();
-    let mut total_blocks = 0;
-
-    let mut show_all = false;
-    let mut long_format = false;
-    let mut show_file_type = false;
-
-    // Sprinkle the arguments
-    for arg in args {
-        match arg.as_str() {
-            "-a" => show_all = true,
-            "-l" => long_format = true,
-            "-F" => show_file_type = true,
-            _ => {
-                eprintln!("ls: invalid option -- '{}'", arg);
-                return;
-            }
+// Line width used by `-C` and `-x` when the output is not a terminal
+const DEFAULT_WIDTH: usize = 80;
+
+// Options offered by tab completion
+pub const FLAGS: [&str; 38] = [
+    "-a",
+    "-l",
+    "-F",
+    "-R",
+    "-r",
+    "-t",
+    "-S",
+    "-X",
+    "-U",
+    "-h",
+    "-C",
+    "-x",
+    "-1",
+    "-i",
+    "-s",
+    "-n",
+    "-g",
+    "-o",
+    "-u",
+    "-c",
+    "--all",
+    "--classify",
+    "--recursive",
+    "--reverse",
+    "--human-readable",
+    "--color=auto",
+    "--color=always",
+    "--color=never",
+    "--inode",
+    "--size",
+    "--numeric-uid-gid",
+    "--time=atime",
+    "--time=ctime",
+    "--time-style=full-iso",
+    "--time-style=long-iso",
+    "--time-style=iso",
+    "--json",
+    "--format=ndjson",
+];
+
+// Flags given on the command line
+#[derive(Default)]
+struct Options {
+    show_all: bool,
+    long_format: bool,
+    show_file_type: bool,
+    recursive: bool,
+    sort: Sort,
+    reverse: bool,
+    human_readable: bool,
+    show_inode: bool,
+    // Allocated size, in kibibytes
+    show_blocks: bool,
+    numeric_ids: bool,
+    hide_owner: bool,
+    hide_group: bool,
+    time: TimeField,
+    time_style: TimeStyle,
+    output: OutputFormat,
+    layout: Option<Layout>,
+    color: ColorMode,
+    // The colors to use, when the listing is colored
+    colors: Option<Colors>,
+    // Columns of the terminal standard output goes to, if it is one
+    terminal_width: Option<usize>,
+}
+
+impl Options {
+    // Columns on a terminal, one name per line when the output is piped
+    fn layout(&self) -> Layout {
+        match (self.layout, self.terminal_width) {
+            (Some(layout), _) => layout,
+            (None, Some(_)) => Layout::Columns,
+            (None, None) => Layout::OneLine,
         }
     }
+}
+
+// The timestamp shown and sorted on
+#[derive(Default, Clone, Copy)]
+enum TimeField {
+    #[default]
+    Modified,
+    Accessed,
+    // Last change of the inode (`ctime`)
+    Changed,
+}
+
+// How timestamps are written in the long format
+#[derive(Default, Clone, Copy)]
+enum TimeStyle {
+    // `Oct 18 12:04`, or `Oct 18  2023` for old files
+    #[default]
+    Locale,
+    // `2024-10-18 12:04:05.123456789 +0200`
+    FullIso,
+    // `2024-10-18 12:04`
+    LongIso,
+    // `10-18 12:04`, or `2023-10-18` for old files
+    Iso,
+}
+
+// What the listing is written as
+#[derive(Default, PartialEq)]
+enum OutputFormat {
+    #[default]
+    Text,
+    // One array of objects (`--json`)
+    Json,
+    // One object per line (`--format=ndjson`)
+    Ndjson,
+}
+
+// When to color names, set with `--color`
+#[derive(Default, PartialEq)]
+enum ColorMode {
+    // Only on a terminal, and unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
 
-    // List the current directory files
-    let current_dir = match fs::read_dir(".") {
-        Ok(entries) => entries,
+// How names are arranged outside of the long format
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+    // Sorted down the columns (`-C`)
+    Columns,
+    // Sorted across the rows (`-x`)
+    Across,
+    OneLine,
+}
+
+// Order of the entries, alphabetical by default
+#[derive(Default)]
+enum Sort {
+    #[default]
+    Name,
+    Time,
+    Size,
+    Extension,
+    // The order in which the directory is read
+    None,
+}
+
+// A name to print along with what is known about the file
+struct Entry {
+    name: String,
+    // Where the file is, for reading the target of a symbolic link
+    path: PathBuf,
+    // From `lstat`, so symbolic links describe themselves
+    metadata: Option<Metadata>,
+}
+
+pub fn ls(
+    args: Vec<String>,
+    terminal_width: Option<usize>,
+    out: &mut dyn Write,
+    err: &mut dyn Write,
+) -> io::Result<i32> {
+    let (mut options, mut operands) = match parse_args(args) {
+        Ok(parsed) => parsed,
         Err(e) => {
-            eprintln!("ls: cannot access current directory: {}", e);
-            return;
+            writeln!(err, "ls: {}", e)?;
+            return Ok(2);
         }
     };
+    options.terminal_width = terminal_width;
+    let colored = match options.color {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            terminal_width.is_some() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+        }
+    };
+    if colored {
+        options.colors = Some(Colors::from_env());
+    }
+    if operands.is_empty() {
+        operands.push(".".to_string());
+    }
 
-    let mut entries: Vec<DirEntry> = Vec::new();
-
-    if show_all {
-        if long_format {
-            //For the long format, add the appropriate metadata
-            if let Ok(metadata) = fs::metadata(".") {
-                result.push_str(&print_metadata(&metadata));
-                if show_file_type {
-                    result.push_str("\x1b[35m./\x1b[0m\n");
+    // Like coreutils, files given as operands come first, then directories
+    let mut status = 0;
+    let mut files = Vec::new();
+    let mut directories = Vec::new();
+    for operand in &operands {
+        match operand_metadata(operand, &options) {
+            Ok((metadata, is_dir)) => {
+                let entry = Entry {
+                    name: operand.clone(),
+                    path: PathBuf::from(operand),
+                    metadata: Some(metadata),
+                };
+                if is_dir {
+                    directories.push(entry);
                 } else {
-                    result.push_str("\x1b[35m.\x1b[0m\n");
+                    files.push(entry);
                 }
-                total_blocks += metadata.blocks() / 2;
             }
-            if let Ok(metadata) = fs::metadata("..") {
-                result.push_str(&print_metadata(&metadata));
-                if show_file_type {
-                    result.push_str("\x1b[35m../\x1b[0m\n");
-                } else {
-                    result.push_str("\x1b[35m..\x1b[0m\n");
-                }
-                total_blocks += metadata.blocks() / 2;
+            Err(e) => {
+                writeln!(err, "ls: cannot access '{}': {}", operand, e)?;
+                status = 2;
+            }
+        }
+    }
+    sort_entries(&mut files, &options);
+    sort_entries(&mut directories, &options);
+
+    // JSON objects are gathered into one array, NDJSON lines are written as
+    // they come
+    let mut objects = Vec::new();
+    let mut emit = |entries: &[Entry], out: &mut dyn Write| -> io::Result<()> {
+        for entry in entries {
+            let object = json_object(entry, &options);
+            match options.output {
+                OutputFormat::Json => objects.push(object),
+                _ => writeln!(out, "{}", object)?,
             }
+        }
+        Ok(())
+    };
+
+    let mut first = true;
+    if !files.is_empty() {
+        if options.output == OutputFormat::Text {
+            write!(out, "{}", format_entries(&files, &options))?;
         } else {
-            result.push_str("\x1b[35m.  ..\x1b[0m  ");
+            emit(&files, out)?;
+        }
+        first = false;
+    }
+
+    // Headers are only needed when there is more than one operand or when
+    // subdirectories are listed too. Directories are visited depth first,
+    // so the stack holds them in reverse order.
+    let headers = operands.len() > 1 || options.recursive;
+    let mut stack: Vec<String> = directories.into_iter().rev().map(|d| d.name).collect();
+    let text = options.output == OutputFormat::Text;
+    while let Some(directory) = stack.pop() {
+        interrupt::check()?;
+        if text && !first {
+            writeln!(out)?;
+        }
+        first = false;
+        if text && headers {
+            writeln!(out, "{}:", directory)?;
+        }
+
+        let entries = match read_directory(&directory, &options) {
+            Ok(entries) => entries,
+            Err(e) => {
+                writeln!(err, "ls: cannot open directory '{}': {}", directory, e)?;
+                status = 2;
+                continue;
+            }
+        };
+        if !text {
+            emit(&entries, out)?;
+        } else if options.long_format || options.show_blocks {
+            let total_blocks: u64 = entries
+                .iter()
+                .filter_map(|entry| entry.metadata.as_ref())
+                .map(|metadata| metadata.blocks() / 2)
+                .sum();
+            if options.human_readable {
+                writeln!(out, "total {}", human_size(total_blocks * 1024))?;
+            } else {
+                writeln!(out, "total {}", total_blocks)?;
+            }
+        }
+        if text {
+            write!(out, "{}", format_entries(&entries, &options))?;
+        }
+
+        if options.recursive {
+            // The metadata of entries is not followed, so symbolic links to
+            // directories are listed but never entered
+            let subdirectories = entries
+                .iter()
+                .filter(|entry| entry.name != "." && entry.name != "..")
+                .filter(|entry| entry.metadata.as_ref().is_some_and(|m| m.is_dir()))
+                .map(|entry| join(&directory, &entry.name));
+            let start = stack.len();
+            stack.extend(subdirectories);
+            stack[start..].reverse();
         }
     }
 
-    for entry in current_dir {
-        if let Ok(entry) = entry {
-            entries.push(entry);
+    if options.output == OutputFormat::Json {
+        if objects.is_empty() {
+            writeln!(out, "[]")?;
+        } else {
+            writeln!(out, "[\n  {}\n]", objects.join(",\n  "))?;
         }
     }
 
-    // Sort the files in alphabetical order
-    entries.sort_by_key(|entry| entry.file_name());
+    Ok(status)
+}
 
-    for entry in entries {
-        let file_name = entry.file_name();
-        let file_name = file_name.to_string_lossy();
-        let mut color = String::new();
+// Split the arguments into options and operands. Short flags can be
+// combined (`-laF`) and everything after `--` is an operand.
+fn parse_args(args: Vec<String>) -> Result<(Options, Vec<String>), String> {
+    let mut options = Options::default();
+    let mut operands = Vec::new();
+    let mut args = args.into_iter();
 
-        if let Ok(metadata) = entry.metadata() {
-            color = right_color(&metadata);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            operands.extend(args.by_ref());
+        } else if let Some(option) = arg.strip_prefix("--") {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (option, None),
+            };
+            match (name, value) {
+                ("all", None) => options.show_all = true,
+                ("classify", None) => options.show_file_type = true,
+                ("recursive", None) => options.recursive = true,
+                ("reverse", None) => options.reverse = true,
+                ("human-readable", None) => options.human_readable = true,
+                ("inode", None) => options.show_inode = true,
+                ("json", None) => options.output = OutputFormat::Json,
+                ("format", Some(value)) => match value {
+                    "json" => options.output = OutputFormat::Json,
+                    "ndjson" => options.output = OutputFormat::Ndjson,
+                    "long" | "verbose" => options.long_format = true,
+                    "single-column" => options.layout = Some(Layout::OneLine),
+                    "vertical" => options.layout = Some(Layout::Columns),
+                    "across" | "horizontal" => options.layout = Some(Layout::Across),
+                    _ => return Err(invalid_argument(value, name)),
+                },
+                ("size", None) => options.show_blocks = true,
+                ("numeric-uid-gid", None) => {
+                    options.long_format = true;
+                    options.numeric_ids = true;
+                }
+                ("time", Some(value)) => {
+                    options.time = match value {
+                        "atime" | "access" | "use" => TimeField::Accessed,
+                        "ctime" | "status" => TimeField::Changed,
+                        "mtime" | "modification" => TimeField::Modified,
+                        _ => return Err(invalid_argument(value, name)),
+                    }
+                }
+                ("time-style", Some(value)) => {
+                    options.time_style = match value {
+                        "full-iso" => TimeStyle::FullIso,
+                        "long-iso" => TimeStyle::LongIso,
+                        "iso" => TimeStyle::Iso,
+                        "locale" => TimeStyle::Locale,
+                        _ => return Err(invalid_argument(value, name)),
+                    }
+                }
+                ("time" | "time-style" | "format", None) => {
+                    return Err(format!("option '--{}' requires an argument", name))
+                }
+                ("color", None) => options.color = ColorMode::Always,
+                ("color", Some(value)) => {
+                    options.color = match value {
+                        "always" | "yes" | "force" => ColorMode::Always,
+                        "never" | "no" | "none" => ColorMode::Never,
+                        "auto" | "tty" | "if-tty" => ColorMode::Auto,
+                        _ => return Err(invalid_argument(value, name)),
+                    }
+                }
+                (
+                    "all" | "classify" | "recursive" | "reverse" | "human-readable" | "inode"
+                    | "size" | "numeric-uid-gid" | "json",
+                    Some(_),
+                ) => return Err(format!("option '--{}' doesn't allow an argument", name)),
+                _ => return Err(format!("unrecognized option '{}'", arg)),
+            }
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            for flag in arg[1..].chars() {
+                match flag {
+                    'a' => options.show_all = true,
+                    'l' => options.long_format = true,
+                    'F' => options.show_file_type = true,
+                    'R' => options.recursive = true,
+                    'r' => options.reverse = true,
+                    'h' => options.human_readable = true,
+                    'i' => options.show_inode = true,
+                    's' => options.show_blocks = true,
+                    'n' => {
+                        options.long_format = true;
+                        options.numeric_ids = true;
+                    }
+                    'g' => {
+                        options.long_format = true;
+                        options.hide_owner = true;
+                    }
+                    'o' => {
+                        options.long_format = true;
+                        options.hide_group = true;
+                    }
+                    'u' => options.time = TimeField::Accessed,
+                    'c' => options.time = TimeField::Changed,
+                    'C' => options.layout = Some(Layout::Columns),
+                    'x' => options.layout = Some(Layout::Across),
+                    '1' => options.layout = Some(Layout::OneLine),
+                    't' => options.sort = Sort::Time,
+                    'S' => options.sort = Sort::Size,
+                    'X' => options.sort = Sort::Extension,
+                    'U' => options.sort = Sort::None,
+                    _ => return Err(format!("invalid option -- '{}'", flag)),
+                }
+            }
+        } else {
+            operands.push(arg);
         }
+    }
 
-        if !show_all && file_name.starts_with('.') {
-            continue;
+    Ok((options, operands))
+}
+
+// The metadata of an operand and whether it is listed as a directory.
+// Symbolic links are followed, so that a link to a directory lists the
+// directory, except in the long format where the link itself is shown.
+// Broken links are listed as they are. Other links are described as links in
+// JSON records, not as the file they point to.
+fn operand_metadata(operand: &str, options: &Options) -> io::Result<(Metadata, bool)> {
+    let metadata = fs::symlink_metadata(operand)?;
+    if !metadata.file_type().is_symlink() || options.long_format {
+        let is_dir = metadata.is_dir();
+        return Ok((metadata, is_dir));
+    }
+
+    let target = fs::metadata(operand).ok();
+    let is_dir = target.as_ref().is_some_and(|m| m.is_dir());
+    match target {
+        Some(target) if options.output == OutputFormat::Text => Ok((target, is_dir)),
+        _ => Ok((metadata, is_dir)),
+    }
+}
+
+fn invalid_argument(value: &str, option: &str) -> String {
+    format!("invalid argument '{}' for '--{}'", value, option)
+}
+
+// Read the entries of one directory, in the requested order
+fn read_directory(path: &str, options: &Options) -> io::Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = fs::read_dir(path)?
+        .flatten()
+        .map(|entry| Entry {
+            name: entry.file_name().to_string_lossy().into_owned(),
+            path: entry.path(),
+            metadata: entry.metadata().ok(),
+        })
+        .filter(|entry| options.show_all || !entry.name.starts_with('.'))
+        .collect();
+
+    sort_entries(&mut entries, options);
+
+    // `.` and `..` always come first
+    if options.show_all {
+        let dir = Path::new(path);
+        for name in ["..", "."] {
+            entries.insert(
+                0,
+                Entry {
+                    name: name.to_string(),
+                    path: dir.join(name),
+                    metadata: fs::metadata(dir.join(name)).ok(),
+                },
+            );
+        }
+    }
+
+    Ok(entries)
+}
+
+fn sort_entries(entries: &mut [Entry], options: &Options) {
+    let modified = |entry: &Entry| {
+        entry
+            .metadata
+            .as_ref()
+            .and_then(|m| timestamp(m, options.time))
+    };
+    let size = |entry: &Entry| entry.metadata.as_ref().map_or(0, |m| m.len());
+
+    // Ties are broken by name; newest and largest files come first
+    match options.sort {
+        Sort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+        Sort::Time => entries.sort_by(|a, b| {
+            modified(b)
+                .cmp(&modified(a))
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        Sort::Size => {
+            entries.sort_by(|a, b| size(b).cmp(&size(a)).then_with(|| a.name.cmp(&b.name)))
         }
+        Sort::Extension => entries.sort_by(|a, b| {
+            extension(&a.name)
+                .cmp(extension(&b.name))
+                .then_with(|| a.name.cmp(&b.name))
+        }),
+        // Directory order cannot be reversed meaningfully
+        Sort::None => return,
+    }
+
+    if options.reverse {
+        entries.reverse();
+    }
+}
+
+// The part of a file name from its last dot, empty when there is none
+fn extension(name: &str) -> &str {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    match name.rfind('.') {
+        Some(i) => &name[i..],
+        None => "",
+    }
+}
+
+fn join(directory: &str, name: &str) -> String {
+    if directory.ends_with('/') {
+        format!("{}{}", directory, name)
+    } else {
+        format!("{}/{}", directory, name)
+    }
+}
+
+fn format_entries(entries: &[Entry], options: &Options) -> String {
+    if options.long_format {
+        return format_long(entries, options);
+    }
 
-        if long_format {
-            if let Ok(metadata) = entry.metadata() {
-                total_blocks += metadata.blocks() / 2;
-                result.push_str(&print_metadata(&metadata));
+    // `-i` and `-s` put aligned numbers before the names
+    let rows: Vec<Vec<Cell>> = entries
+        .iter()
+        .map(|entry| prefix_cells(entry.metadata.as_ref(), options))
+        .collect();
+    let names: Vec<String> = entries
+        .iter()
+        .zip(align_cells(&rows))
+        .map(|(entry, prefix)| prefix + &format_name(entry, options))
+        .collect();
+    match options.layout() {
+        Layout::OneLine => names.iter().map(|name| format!("{}\n", name)).collect(),
+        layout => format_grid(
+            &names,
+            layout == Layout::Across,
+            options.terminal_width.unwrap_or(DEFAULT_WIDTH),
+        ),
+    }
+}
+
+fn format_long(entries: &[Entry], options: &Options) -> String {
+    let mut result = String::new();
+
+    let rows: Vec<Vec<Cell>> = entries
+        .iter()
+        .map(|entry| {
+            let mut cells = prefix_cells(entry.metadata.as_ref(), options);
+            if let Some(metadata) = &entry.metadata {
+                cells.extend(print_metadata(metadata, options));
+            }
+            cells
+        })
+        .collect();
+
+    for (entry, prefix) in entries.iter().zip(align_cells(&rows)) {
+        result.push_str(&prefix);
+        result.push_str(&format_name(entry, options));
+        if entry
+            .metadata
+            .as_ref()
+            .is_some_and(|m| m.file_type().is_symlink())
+        {
+            if let Ok(target) = fs::read_link(&entry.path) {
+                let target = target.to_string_lossy();
+                // The target is colored as what it points to
+                let target = match &options.colors {
+                    Some(colors) => {
+                        let metadata = fs::metadata(&entry.path).ok();
+                        colors.paint(&target, metadata.as_ref(), &entry.path)
+                    }
+                    None => target.into_owned(),
+                };
+                result.push_str(&format!(" -> {}", target));
             }
         }
+        result.push('\n');
+    }
+
+    result
+}
+
+// Pad every column to its widest cell, each followed by a space
+fn align_cells(rows: &[Vec<Cell>]) -> Vec<String> {
+    let mut widths: Vec<usize> = Vec::new();
+    for cells in rows {
+        widths.resize(widths.len().max(cells.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.text.chars().count());
+        }
+    }
+
+    rows.iter()
+        .map(|cells| {
+            let mut line = String::new();
+            for (cell, &width) in cells.iter().zip(&widths) {
+                if cell.right_aligned {
+                    line.push_str(&format!("{:>width$} ", cell.text));
+                } else {
+                    line.push_str(&format!("{:<width$} ", cell.text));
+                }
+            }
+            line
+        })
+        .collect()
+}
+
+// The inode number and allocated size shown before the other columns
+fn prefix_cells(metadata: Option<&Metadata>, options: &Options) -> Vec<Cell> {
+    let mut cells = Vec::new();
+    if options.show_inode {
+        let inode = metadata.map_or("?".to_string(), |m| m.ino().to_string());
+        cells.push(Cell::right(inode));
+    }
+    if options.show_blocks {
+        // `st_blocks` counts 512-byte units
+        let blocks = metadata.map_or("?".to_string(), |m| {
+            if options.human_readable {
+                human_size(m.blocks() * 512)
+            } else {
+                (m.blocks() / 2).to_string()
+            }
+        });
+        cells.push(Cell::right(blocks));
+    }
+    cells
+}
+
+// The colored name of an entry, with its type indicator for `-F`
+fn format_name(entry: &Entry, options: &Options) -> String {
+    let name = match &options.colors {
+        Some(colors) => colors.paint(&entry.name, entry.metadata.as_ref(), &entry.path),
+        None => entry.name.clone(),
+    };
+    match &entry.metadata {
+        // The arrow to the target already says that it is a link
+        Some(metadata) if options.long_format && metadata.file_type().is_symlink() => name,
+        Some(metadata) if options.show_file_type => name + indicator(metadata),
+        _ => name,
+    }
+}
+
+// Lay names out in as many columns as fit in the width, filling columns
+// first (`-C`) or rows first (`-x`), two spaces apart
+fn format_grid(names: &[String], across: bool, width: usize) -> String {
+    let widths: Vec<usize> = names.iter().map(|name| visible_width(name)).collect();
+    let count = names.len();
 
-        if show_file_type {
-            if let Ok(metadata) = entry.metadata() {
-                result.push_str(&print_file_type(&metadata, &file_name));
+    // Try the most columns first; a single column always fits
+    let most = count.min(width / 3 + 1);
+    let (rows, columns, column_widths) = (1..=most)
+        .rev()
+        .find_map(|columns| {
+            let rows = count.div_ceil(columns);
+            // Filling columns first may leave the last ones empty
+            let columns = if across {
+                columns
             } else {
-                result.push_str(&format!("{}{}{}", color, file_name, RESET));
+                count.div_ceil(rows)
+            };
+            let mut column_widths = vec![0; columns];
+            for (i, &name_width) in widths.iter().enumerate() {
+                let column = if across { i % columns } else { i / rows };
+                column_widths[column] = column_widths[column].max(name_width);
             }
+            let total = column_widths.iter().sum::<usize>() + 2 * (columns - 1);
+            (total <= width || columns == 1).then_some((rows, columns, column_widths))
+        })
+        .unwrap_or((0, 0, Vec::new()));
+
+    let index = |row: usize, column: usize| {
+        if across {
+            row * columns + column
         } else {
-            result.push_str(&format!("{}{}{}", color, file_name, RESET));
+            column * rows + row
+        }
+    };
+    let mut result = String::new();
+    for row in 0..rows {
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let i = index(row, column);
+            if i >= count {
+                break;
+            }
+            result.push_str(&names[i]);
+            if column + 1 < columns && index(row, column + 1) < count {
+                result.push_str(&" ".repeat(column_width - widths[i] + 2));
+            }
         }
+        result.push('\n');
+    }
+    result
+}
 
-        if !long_format {
-            result.push_str("  ");
+// Width of a name on screen, without its color escape sequences
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip up to the final letter of the sequence
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
         } else {
-            result.push_str("\n");
+            width += 1;
         }
     }
-    if !long_format {
-        result.push_str("\n");
+    width
+}
+
+// Everything known about an entry as a JSON object on one line
+fn json_object(entry: &Entry, options: &Options) -> String {
+    let mut fields = vec![
+        format!("\"name\":{}", json_string(&entry.name)),
+        format!("\"path\":{}", json_string(&entry.path.to_string_lossy())),
+    ];
+
+    if let Some(metadata) = &entry.metadata {
+        let (owner, group) = get_owner_and_group(metadata, options.numeric_ids);
+        let time = |field| match timestamp(metadata, field) {
+            Some(time) => json_string(&iso_timestamp(time, true)),
+            None => "null".to_string(),
+        };
+        let target = match fs::read_link(&entry.path) {
+            Ok(target) if metadata.file_type().is_symlink() => {
+                json_string(&target.to_string_lossy())
+            }
+            _ => "null".to_string(),
+        };
+
+        fields.extend([
+            format!("\"type\":\"{}\"", type_name(metadata)),
+            format!("\"mode\":\"{:04o}\"", metadata.mode() & 0o7777),
+            format!("\"permissions\":\"{}\"", format_permissions(metadata)),
+            format!("\"inode\":{}", metadata.ino()),
+            format!("\"nlink\":{}", metadata.nlink()),
+            format!("\"uid\":{}", metadata.uid()),
+            format!("\"gid\":{}", metadata.gid()),
+            format!("\"owner\":{}", json_string(&owner)),
+            format!("\"group\":{}", json_string(&group)),
+            format!("\"size\":{}", metadata.len()),
+            format!("\"blocks\":{}", metadata.blocks()),
+            format!("\"modified\":{}", time(TimeField::Modified)),
+            format!("\"accessed\":{}", time(TimeField::Accessed)),
+            format!("\"changed\":{}", time(TimeField::Changed)),
+            format!("\"target\":{}", target),
+        ]);
+    }
+
+    format!("{{{}}}", fields.join(","))
+}
+
+fn type_name(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_char_device() {
+        "char_device"
+    } else if file_type.is_block_device() {
+        "block_device"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
     } else {
-        println!("total {}", total_blocks);
+        "file"
     }
+}
 
-    print!("{}", result);
+// A quoted JSON string, with quotes, backslashes and control characters
+// escaped
+fn json_string(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
 }
 
-fn print_metadata(metadata: &Metadata) -> String {
+// One column of the long format for one file
+struct Cell {
+    text: String,
+    // Numbers are aligned on the right, names on the left
+    right_aligned: bool,
+}
+
+impl Cell {
+    fn left(text: String) -> Cell {
+        Cell {
+            text,
+            right_aligned: false,
+        }
+    }
+
+    fn right(text: String) -> Cell {
+        Cell {
+            text,
+            right_aligned: true,
+        }
+    }
+}
+
+fn print_metadata(metadata: &Metadata, options: &Options) -> Vec<Cell> {
     let permissions = format_permissions(metadata);
     let link_count = get_link_count(metadata);
-    let owner_group = get_owner_and_group(metadata);
-    let size = metadata.len();
+    let (owner, group) = get_owner_and_group(metadata, options.numeric_ids);
+    let file_type = metadata.file_type();
+    let size = if file_type.is_char_device() || file_type.is_block_device() {
+        // Devices have numbers instead of a size
+        let (major, minor) = device_numbers(metadata.rdev());
+        format!("{}, {}", major, minor)
+    } else if options.human_readable {
+        human_size(metadata.len())
+    } else {
+        metadata.len().to_string()
+    };
 
-    let modified = match metadata.modified() {
-        Ok(time) => format_time(time),
-        Err(_) => "unknown".to_string(),
+    let time = match timestamp(metadata, options.time) {
+        Some(time) => format_time(time, options.time_style),
+        None => "unknown".to_string(),
     };
 
-    format!(
-        "{} {:1} {:<8} {:>5} {} ",
-        permissions, link_count, owner_group, size, modified,
-    )
+    let mut cells = vec![Cell::left(permissions), Cell::right(link_count.to_string())];
+    if !options.hide_owner {
+        cells.push(Cell::left(owner));
+    }
+    if !options.hide_group {
+        cells.push(Cell::left(group));
+    }
+    cells.push(Cell::right(size));
+    cells.push(Cell::left(time));
+    cells
 }
 
-fn format_time(time: SystemTime) -> String {
-    // get the seconds from Unix_Epoch
-    let duration = match time.duration_since(UNIX_EPOCH) {
-        Ok(duration) => duration,
-        Err(_) => return "date invalide".to_string(),
-    };
+// Sizes like GNU `ls -h`: powers of 1024, rounded up, with one decimal
+// below 10
+fn human_size(bytes: u64) -> String {
+    const UNITS: [char; 6] = ['K', 'M', 'G', 'T', 'P', 'E'];
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
 
-    // Convert into date components
-    let total_secs = duration.as_secs();
-    let total_mins = total_secs / 60;
-    let mins = total_mins % 60;
-    let total_hours = total_mins / 60;
-    let hours = total_hours % 24;
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
 
-    // Calculate the days since 1970-01-01
-    let days_since_epoch = (total_hours / 24) as u32;
+    let tenths = (value * 10.0).ceil();
+    if tenths < 100.0 {
+        format!("{:.1}{}", tenths / 10.0, UNITS[unit])
+    } else {
+        format!("{}{}", value.ceil(), UNITS[unit])
+    }
+}
 
-    let days_in_month = [31, 28, 31, 30, 31, 30, 31, 31, 30, 31, 30, 31];
-    let mois = [
-        "Jan", "Feb", "Mar", "Apr", "May", "June", "July", "Aug", "Sept", "Oct", "Nov", "Dec",
-    ];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
 
-    // Calculate the month and the day
-    let mut remaining_days = days_since_epoch;
+// Files older than this (half an average Gregorian year) show their year
+const SIX_MONTHS: i64 = 31_556_952 / 2;
 
-    // Adjust for bissextile years
-    for y in 1970..=2024 {
-        if (y % 4 == 0 && y % 100 != 0) || (y % 400 == 0) {
-            if remaining_days >= 366 {
-                remaining_days -= 366;
+// The timestamp selected with `--time`
+fn timestamp(metadata: &Metadata, field: TimeField) -> Option<SystemTime> {
+    match field {
+        TimeField::Modified => metadata.modified().ok(),
+        TimeField::Accessed => metadata.accessed().ok(),
+        TimeField::Changed => {
+            let since_epoch = Duration::new(
+                metadata.ctime().unsigned_abs(),
+                metadata.ctime_nsec() as u32,
+            );
+            if metadata.ctime() >= 0 {
+                UNIX_EPOCH.checked_add(since_epoch)
+            } else {
+                UNIX_EPOCH.checked_sub(since_epoch)
             }
-        } else if remaining_days >= 365 {
-            remaining_days -= 365;
-        }
-        if remaining_days < 365 {
-            break;
         }
     }
+}
+
+// Date in the local timezone. Like GNU ls, the default style shows the time
+// of day for recent files and the year for old files and files in the future.
+fn format_time(time: SystemTime, style: TimeStyle) -> String {
+    let secs = epoch_seconds(time);
+    let offset = utc_offset(secs);
+    let local = secs + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    let seconds_of_day = local.rem_euclid(86_400);
+    let (hours, minutes) = (seconds_of_day / 3600, seconds_of_day % 3600 / 60);
 
-    // Find the month and the day
-    let mut month = 0;
-    for (i, &days) in days_in_month.iter().enumerate() {
-        if remaining_days < days {
-            month = i;
-            break;
+    let now = epoch_seconds(SystemTime::now());
+    let recent = secs <= now && now - secs <= SIX_MONTHS;
+    match style {
+        TimeStyle::FullIso => iso_timestamp(time, false),
+        TimeStyle::LongIso => format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            year, month, day, hours, minutes
+        ),
+        TimeStyle::Iso if recent => {
+            format!("{:02}-{:02} {:02}:{:02}", month, day, hours, minutes)
         }
-        remaining_days -= days;
+        TimeStyle::Iso => format!("{:04}-{:02}-{:02} ", year, month, day),
+        TimeStyle::Locale if recent => format!(
+            "{} {:>2} {:02}:{:02}",
+            MONTHS[month - 1],
+            day,
+            hours,
+            minutes
+        ),
+        TimeStyle::Locale => format!("{} {:>2}  {}", MONTHS[month - 1], day, year),
     }
-    let day = remaining_days + 1;
+}
+
+// A local time with nanoseconds and the UTC offset, either GNU's full-iso
+// `2024-10-18 12:04:05.123456789 +0200` or RFC 3339
+// `2024-10-18T12:04:05.123456789+02:00`
+fn iso_timestamp(time: SystemTime, rfc3339: bool) -> String {
+    let secs = epoch_seconds(time);
+    let offset = utc_offset(secs);
+    let local = secs + offset;
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    let seconds_of_day = local.rem_euclid(86_400);
+    let nanos = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.subsec_nanos());
+
+    let sign = if offset < 0 { '-' } else { '+' };
+    let (offset_hours, offset_minutes) = (offset.abs() / 3600, offset.abs() % 3600 / 60);
+    let (separator, zone) = if rfc3339 {
+        (
+            'T',
+            format!("{}{:02}:{:02}", sign, offset_hours, offset_minutes),
+        )
+    } else {
+        (
+            ' ',
+            format!(" {}{:02}{:02}", sign, offset_hours, offset_minutes),
+        )
+    };
+    format!(
+        "{:04}-{:02}-{:02}{}{:02}:{:02}:{:02}.{:09}{}",
+        year,
+        month,
+        day,
+        separator,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+        nanos,
+        zone
+    )
+}
 
-    format!("{} {:>2} {:02}:{:02}", mois[month], day, hours % 24, mins)
+// Seconds since 1970-01-01 UTC, negative for earlier times
+fn epoch_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
 }
 
-fn right_color(metadata: &Metadata) -> String {
-    if metadata.is_dir() {
-        format!("{}", PINK)
-    } else if metadata.permissions().mode() & 0o111 != 0 {
-        format!("{}", ORANGE)
-    } else if metadata.file_type().is_symlink() {
-        format!("{}", CYAN)
+// Offset of the local timezone from UTC at a given time, in seconds
+fn utc_offset(secs: i64) -> i64 {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        0
     } else {
-        format!("{}", RESET)
+        tm.tm_gmtoff as i64
     }
 }
 
-fn print_file_type(metadata: &Metadata, file_name: &str) -> String {
-    if metadata.is_dir() {
-        format!("{}{}/{}", PINK, file_name, RESET)
+// Split a device id into its major and minor numbers, in the layout of
+// glibc's `makedev`: the low 8 bits of the minor, 12 bits of the major, then
+// the rest of the minor and the rest of the major.
+fn device_numbers(device: u64) -> (u64, u64) {
+    let major = ((device >> 32) & 0xffff_f000) | ((device >> 8) & 0x0fff);
+    let minor = ((device >> 12) & 0xffff_ff00) | (device & 0x00ff);
+    (major, minor)
+}
+
+// Year, month (1-12) and day of the month of a day counted from 1970-01-01,
+// in the proleptic Gregorian calendar
+fn civil_from_days(days: i64) -> (i64, usize, i64) {
+    // Count from 0000-03-01 so that leap days end the 400-year eras
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as usize, day)
+}
+
+// Colors of file types and extensions, from a `LS_COLORS` specification
+// like `di=01;34:ln=01;36:*.tar=01;31`
+struct Colors {
+    // Two-letter type codes such as `di` or `ex`
+    types: HashMap<String, String>,
+    // `*.ext` patterns, in the order they were given
+    extensions: Vec<(String, String)>,
+}
+
+impl Colors {
+    // The default colors, overridden by the ones in `LS_COLORS`
+    fn from_env() -> Colors {
+        let mut colors = Colors {
+            types: HashMap::new(),
+            extensions: Vec::new(),
+        };
+        colors.parse(DEFAULT_COLORS);
+        if let Ok(spec) = env::var("LS_COLORS") {
+            colors.parse(&spec);
+        }
+        colors
+    }
+
+    fn parse(&mut self, spec: &str) {
+        for item in spec.split(':') {
+            let Some((key, code)) = item.split_once('=') else {
+                continue;
+            };
+            match key.strip_prefix('*') {
+                Some(suffix) => self.extensions.push((suffix.to_string(), code.to_string())),
+                None => {
+                    self.types.insert(key.to_string(), code.to_string());
+                }
+            }
+        }
+    }
+
+    // Wrap a name in the escape sequences of its color. The metadata is that
+    // of the file itself, not followed, and missing for broken entries.
+    fn paint(&self, name: &str, metadata: Option<&Metadata>, path: &Path) -> String {
+        match self.code(name, metadata, path) {
+            Some(code) if !code.is_empty() => {
+                let reset = self.types.get("rs").map_or("0", String::as_str);
+                format!("\x1b[{}m{}\x1b[{}m", code, name, reset)
+            }
+            _ => name.to_string(),
+        }
+    }
+
+    fn code(&self, name: &str, metadata: Option<&Metadata>, path: &Path) -> Option<&str> {
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => return self.get(&["mi", "or"]),
+        };
+        let file_type = metadata.file_type();
+        let mode = metadata.permissions().mode();
+
+        if file_type.is_symlink() {
+            return match fs::metadata(path) {
+                // `ln=target` colors links like the file they point to
+                Ok(target) if self.get(&["ln"]) == Some("target") => {
+                    self.code(name, Some(&target), path)
+                }
+                Ok(_) => self.get(&["ln"]),
+                // Without a target, `ln=target` leaves broken links uncolored
+                Err(_) => self
+                    .get(&["or"])
+                    .or_else(|| self.get(&["ln"]).filter(|&code| code != "target")),
+            };
+        }
+        if file_type.is_dir() {
+            let sticky = mode & 0o1000 != 0;
+            let writable = mode & 0o002 != 0;
+            return match (sticky, writable) {
+                (true, true) => self.get(&["tw", "ow", "st", "di"]),
+                (false, true) => self.get(&["ow", "di"]),
+                (true, false) => self.get(&["st", "di"]),
+                (false, false) => self.get(&["di"]),
+            };
+        }
+        if file_type.is_fifo() {
+            return self.get(&["pi"]);
+        }
+        if file_type.is_socket() {
+            return self.get(&["so"]);
+        }
+        if file_type.is_block_device() {
+            return self.get(&["bd"]);
+        }
+        if file_type.is_char_device() {
+            return self.get(&["cd"]);
+        }
+
+        // Regular files: special bits first, then the extension
+        let mut keys = Vec::new();
+        if mode & 0o4000 != 0 {
+            keys.push("su");
+        }
+        if mode & 0o2000 != 0 {
+            keys.push("sg");
+        }
+        if mode & 0o111 != 0 {
+            keys.push("ex");
+        }
+        if metadata.nlink() > 1 {
+            keys.push("mh");
+        }
+        if let Some(code) = self.get(&keys) {
+            return Some(code);
+        }
+        self.extensions
+            .iter()
+            .rev()
+            .find(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .map(|(_, code)| code.as_str())
+            .or_else(|| self.get(&["fi"]))
+    }
+
+    // The first of the type codes that has a color
+    fn get(&self, keys: &[&str]) -> Option<&str> {
+        keys.iter()
+            .find_map(|key| self.types.get(*key))
+            .map(String::as_str)
+    }
+}
+
+// The character `-F` appends to a name to show its type
+fn indicator(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        "@"
+    } else if file_type.is_dir() {
+        "/"
+    } else if file_type.is_fifo() {
+        "|"
+    } else if file_type.is_socket() {
+        "="
     } else if metadata.permissions().mode() & 0o111 != 0 {
-        format!("{}{}*{}", ORANGE, file_name, RESET)
-    } else if metadata.file_type().is_symlink() {
-        format!("{}{}@{}", CYAN, file_name, RESET)
+        "*"
     } else {
-        format!("{}", file_name)
+        ""
     }
 }
 
@@ -221,55 +1195,78 @@ fn format_permissions(metadata: &Metadata) -> String {
     let mode = permissions.mode();
 
     // File type
-    let file_type = if metadata.is_dir() {
+    let file_type = metadata.file_type();
+    let type_char = if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_dir() {
         'd'
-    } else if metadata.is_file() {
-        '-'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
     } else {
-        '?'
+        '-'
     };
 
-    // User permissions (Owner)
+    // User permissions (Owner), `s` when setuid
     let user = format!(
         "{}{}{}",
         if mode & 0o400 != 0 { 'r' } else { '-' }, // Read
         if mode & 0o200 != 0 { 'w' } else { '-' }, // Write
-        if mode & 0o100 != 0 { 'x' } else { '-' }  // Execute
+        execute_char(mode & 0o100 != 0, mode & 0o4000 != 0, 's')
     );
 
-    // PermissionsGroupe (group)
+    // PermissionsGroupe (group), `s` when setgid
     let group = format!(
         "{}{}{}",
         if mode & 0o040 != 0 { 'r' } else { '-' }, // Read
         if mode & 0o020 != 0 { 'w' } else { '-' }, // Write
-        if mode & 0o010 != 0 { 'x' } else { '-' }  // Execute
+        execute_char(mode & 0o010 != 0, mode & 0o2000 != 0, 's')
     );
 
-    // PermissionsAutres (others)
+    // PermissionsAutres (others), `t` when sticky
     let others = format!(
         "{}{}{}",
         if mode & 0o004 != 0 { 'r' } else { '-' }, // Read
         if mode & 0o002 != 0 { 'w' } else { '-' }, // Write
-        if mode & 0o001 != 0 { 'x' } else { '-' }  // Execute
+        execute_char(mode & 0o001 != 0, mode & 0o1000 != 0, 't')
     );
 
     //Combine the type of file and permissions
-    format!("{}{}{}{}", file_type, user, group, others)
+    format!("{}{}{}{}", type_char, user, group, others)
+}
+
+// The execute position also shows the special bits: lowercase when the file
+// is executable too, uppercase when it is not
+fn execute_char(execute: bool, special: bool, letter: char) -> char {
+    match (execute, special) {
+        (true, true) => letter,
+        (false, true) => letter.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    }
 }
 
 fn get_link_count(metadata: &Metadata) -> u64 {
     metadata.nlink()
 }
 
-fn get_owner_and_group(metadata: &Metadata) -> String {
+fn get_owner_and_group(metadata: &Metadata, numeric: bool) -> (String, String) {
     let uid = metadata.uid();
     let gid = metadata.gid();
+    if numeric {
+        return (uid.to_string(), gid.to_string());
+    }
 
-    // Recover the owner's name (User)
+    // Recover the owner's name (User), or its number without one
     let user_name = unsafe {
         let pw = libc::getpwuid(uid);
         if pw.is_null() {
-            "unknown".to_string()
+            uid.to_string()
         } else {
             let user = CStr::from_ptr((*pw).pw_name).to_string_lossy().into_owned();
             user
@@ -280,12 +1277,57 @@ fn get_owner_and_group(metadata: &Metadata) -> String {
     let group_name = unsafe {
         let gr = libc::getgrgid(gid);
         if gr.is_null() {
-            "unknown".to_string()
+            gid.to_string()
         } else {
             let group = CStr::from_ptr((*gr).gr_name).to_string_lossy().into_owned();
             group
         }
     };
 
-    format!("{} {}", user_name, group_name)
+    (user_name, group_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn epoch() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+    }
+
+    #[test]
+    fn leap_days() {
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(19_783), (2024, 3, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+    }
+
+    #[test]
+    fn day_after_february() {
+        assert_eq!(civil_from_days(20_148), (2025, 3, 1));
+        assert_eq!(civil_from_days(20_147), (2025, 2, 28));
+    }
+
+    #[test]
+    fn century_without_leap_day() {
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+    }
+
+    #[test]
+    fn device_ids() {
+        assert_eq!(device_numbers(0x0103), (1, 3));
+        assert_eq!(device_numbers(0x8801), (136, 1));
+        // Numbers too large for the old 16-bit layout
+        assert_eq!(device_numbers(0x1000_001f_ffff), (8_191, 511));
+        assert_eq!(device_numbers(0x1001_0300), (259, 65_536));
+    }
+
+    #[test]
+    fn before_epoch() {
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(-25_509), (1900, 2, 28));
+        assert_eq!(civil_from_days(-25_508), (1900, 3, 1));
+    }
 }
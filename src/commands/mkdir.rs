@@ -1,13 +1,20 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
 
-pub fn mkdir(args: Vec<String>) {
+// Options offered by tab completion
+pub const FLAGS: [&str; 1] = ["-p"];
+
+pub fn mkdir(args: Vec<String>, err: &mut dyn Write) -> io::Result<i32> {
     if args.is_empty() {
-        eprintln!("mkdir: missing operand");
-        return;
+        writeln!(err, "mkdir: missing operand")?;
+        return Ok(1);
     }
 
     let mut recursive = false;
-    let mut paths:Vec<String> = Vec::new();
+    let mut paths: Vec<String> = Vec::new();
 
     // Handle the flag -p
     for arg in args {
@@ -18,6 +25,7 @@ pub fn mkdir(args: Vec<String>) {
         }
     }
 
+    let mut status = 0;
     for path in paths {
         let path = Path::new(&path);
         let result = if recursive {
@@ -26,9 +34,15 @@ pub fn mkdir(args: Vec<String>) {
             fs::create_dir(path) // Create a single directory
         };
 
-        match result {
-            Ok(_) => {}
-            Err(e) => eprintln!("mkdir: cannot create directory '{}': {}", path.display(), e),
+        if let Err(e) = result {
+            writeln!(
+                err,
+                "mkdir: cannot create directory '{}': {}",
+                path.display(),
+                e
+            )?;
+            status = 1;
         }
     }
+    Ok(status)
 }
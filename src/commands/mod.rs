@@ -7,6 +7,11 @@ mod cp;
 mod rm;
 mod mv;
 mod mkdir;
+mod export;
+mod unset;
+mod env;
+mod history;
+mod options;
 
 pub use echo::echo;
 pub use ls::ls;
@@ -16,4 +21,22 @@ pub use cat::cat;
 pub use cp::cp;
 pub use rm::rm;
 pub use mv::mv;
-pub use mkdir::mkdir;
\ No newline at end of file
+pub use mkdir::mkdir;
+pub use export::export;
+pub use unset::unset;
+pub use env::env;
+pub use history::history;
+
+// The options of a builtin, for tab completion
+pub fn flags(command: &str) -> Vec<String> {
+    let flags: &[&str] = match command {
+        "echo" => &echo::FLAGS,
+        "ls" => &ls::FLAGS,
+        "mkdir" => &mkdir::FLAGS,
+        "cp" => return cp::SPEC.completions(),
+        "rm" => return rm::SPEC.completions(),
+        "mv" => return mv::SPEC.completions(),
+        _ => &[],
+    };
+    flags.iter().map(|flag| flag.to_string()).collect()
+}
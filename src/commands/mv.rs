@@ -1,31 +1,72 @@
+use super::options::{may_replace, parse_flags, split_operands, FlagSpec, Overwrite};
 use std::fs;
-use std::io;
+use std::io::{self, Write};
 use std::path::Path;
 
-pub fn mv(args: Vec<String>) {
-    if args.len() != 2 {
-        println!("mv: missing file operand");
-        println!("Usage: mv <source> <destination>");
-        return;
-    }
-    let source = &args[0];
-    let destination = &args[1];
-    if let Err(err) = move_file(&source, &destination) {
-        println!("mv: {}", err);
+// Flags accepted on the command line, also offered by tab completion
+pub const SPEC: FlagSpec = FlagSpec {
+    short: "infu",
+    long: &[
+        ("interactive", 'i'),
+        ("no-clobber", 'n'),
+        ("force", 'f'),
+        ("update", 'u'),
+    ],
+    with_value: &[],
+};
+
+// What to do with existing destinations
+#[derive(Default)]
+struct Options {
+    overwrite: Overwrite,
+    // Only replace older files (`-u`)
+    update: bool,
+}
+
+pub fn mv(args: Vec<String>, err: &mut dyn Write) -> io::Result<i32> {
+    let mut options = Options::default();
+    let operands = parse_flags("mv", args, &SPEC, err, |flag, _| {
+        match flag {
+            'i' => options.overwrite = Overwrite::Ask,
+            'n' => options.overwrite = Overwrite::Never,
+            'f' => options.overwrite = Overwrite::Always,
+            'u' => options.update = true,
+            _ => {}
+        }
+        Ok(())
+    })?;
+    let operands = match operands {
+        Some(operands) => operands,
+        None => return Ok(1),
+    };
+
+    let usage = "mv [-finu] <source>... <destination>";
+    let (sources, destination) = match split_operands("mv", usage, &operands, err)? {
+        Some(split) => split,
+        None => return Ok(1),
+    };
+
+    let mut status = 0;
+    for source in sources {
+        if let Err(e) = move_file(source, destination, &options) {
+            writeln!(err, "mv: {}", e)?;
+            status = 1;
+        }
     }
+    Ok(status)
 }
 
-fn move_file(source: &str, destination: &str) -> io::Result<()> {
+fn move_file(source: &str, destination: &str, options: &Options) -> io::Result<()> {
     let source_path = Path::new(source);
     let destination_path = Path::new(destination);
 
-    // Check if the source exists
-    if !source_path.exists() {
-        return Err(io::Error::new(
+    // Check if the source exists (a broken symbolic link can still be moved)
+    let metadata = fs::symlink_metadata(source_path).map_err(|_| {
+        io::Error::new(
             io::ErrorKind::NotFound,
             format!("cannot stat '{}': No such file or directory", source),
-        ));
-    }
+        )
+    })?;
 
     // Determine the final destination path
     let final_destination = if destination_path.is_dir() {
@@ -36,14 +77,28 @@ fn move_file(source: &str, destination: &str) -> io::Result<()> {
         destination_path.to_path_buf()
     };
 
-    // Check if the parent directory of the destination exists
-    if !final_destination.parent().map_or(true, |p| p.exists()) {
+    // Check if the parent directory of the destination exists (a bare name
+    // is in the current directory)
+    let parent_exists = final_destination
+        .parent()
+        .is_none_or(|p| p.as_os_str().is_empty() || p.exists());
+    if !parent_exists {
         return Err(io::Error::new(
             io::ErrorKind::NotFound,
             format!("cannot move '{}': No such directory", destination),
         ));
     }
 
+    if !may_replace(
+        "mv",
+        &metadata,
+        &final_destination,
+        options.overwrite,
+        options.update,
+    ) {
+        return Ok(());
+    }
+
     // try to rename first
     match fs::rename(source_path, &final_destination) {
         Ok(_) => Ok(()),
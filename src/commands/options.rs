@@ -0,0 +1,257 @@
+use std::fs::{self, Metadata, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+// What `cp` and `mv` do when the destination already exists. The last of
+// -f, -i and -n given wins.
+#[derive(Default, Clone, Copy)]
+pub enum Overwrite {
+    #[default]
+    Always,
+    // Ask first (`-i`)
+    Ask,
+    // Keep the existing file (`-n`)
+    Never,
+}
+
+// Whether `cp` or `mv` may replace the destination of a file. With `update`
+// (`-u`) an existing destination is only replaced by a newer source.
+pub fn may_replace(
+    command: &str,
+    source: &Metadata,
+    destination: &Path,
+    overwrite: Overwrite,
+    update: bool,
+) -> bool {
+    let existing = match fs::symlink_metadata(destination) {
+        Ok(existing) => existing,
+        Err(_) => return true,
+    };
+    if update && existing.modified().ok() >= source.modified().ok() {
+        return false;
+    }
+    match overwrite {
+        Overwrite::Always => true,
+        Overwrite::Ask => confirm(&format!(
+            "{}: overwrite '{}'? ",
+            command,
+            destination.display()
+        )),
+        Overwrite::Never => false,
+    }
+}
+
+// Ask a yes/no question on the terminal, even when the standard streams are
+// redirected. Without a terminal the answer is no.
+pub fn confirm(question: &str) -> bool {
+    let mut tty = match OpenOptions::new().read(true).write(true).open("/dev/tty") {
+        Ok(tty) => tty,
+        Err(_) => return false,
+    };
+    if write!(tty, "{}", question)
+        .and_then(|_| tty.flush())
+        .is_err()
+    {
+        return false;
+    }
+
+    // One byte at a time so that nothing after the line is consumed
+    let mut answer = Vec::new();
+    let mut byte = [0u8; 1];
+    while let Ok(1) = tty.read(&mut byte) {
+        if byte[0] == b'\n' {
+            break;
+        }
+        answer.push(byte[0]);
+    }
+    matches!(
+        String::from_utf8_lossy(&answer).trim_start().chars().next(),
+        Some('y' | 'Y')
+    )
+}
+
+// Split the operands of `cp` and `mv` into the sources and the destination.
+// A missing operand, or several sources with a destination that is not a
+// directory, is reported and gives `None`.
+pub fn split_operands<'a>(
+    command: &str,
+    usage: &str,
+    operands: &'a [String],
+    err: &mut dyn Write,
+) -> io::Result<Option<(&'a [String], &'a str)>> {
+    let (sources, destination) = match operands.split_last() {
+        Some((destination, sources)) if !sources.is_empty() => (sources, destination),
+        Some((source, _)) => {
+            writeln!(
+                err,
+                "{}: missing destination file operand after '{}'",
+                command, source
+            )?;
+            return Ok(None);
+        }
+        None => {
+            writeln!(err, "{}: missing file operand", command)?;
+            writeln!(err, "Usage: {}", usage)?;
+            return Ok(None);
+        }
+    };
+
+    if sources.len() > 1 && !Path::new(destination).is_dir() {
+        writeln!(
+            err,
+            "{}: target '{}' is not a directory",
+            command, destination
+        )?;
+        return Ok(None);
+    }
+    Ok(Some((sources, destination)))
+}
+
+// The flags a builtin accepts
+pub struct FlagSpec {
+    // Short flags, which can be combined like `-rf`
+    pub short: &'static str,
+    // Long options, without their dashes, and the short flag each stands for
+    pub long: &'static [(&'static str, char)],
+    // Short flags whose long option may be given a value, like `--preserve=mode`
+    pub with_value: &'static [char],
+}
+
+impl FlagSpec {
+    // Every flag as offered by tab completion
+    pub fn completions(&self) -> Vec<String> {
+        let short = self.short.chars().map(|flag| format!("-{}", flag));
+        let long = self.long.iter().map(|(name, _)| format!("--{}", name));
+        short.chain(long).collect()
+    }
+}
+
+// Parse the arguments of `cp`, `mv` and `rm`, handing each flag to `apply`
+// along with the value of its long option if any. Mistakes are reported and
+// give `None`; otherwise the remaining operands are returned.
+pub fn parse_flags(
+    command: &str,
+    args: Vec<String>,
+    spec: &FlagSpec,
+    err: &mut dyn Write,
+    mut apply: impl FnMut(char, Option<&str>) -> Result<(), String>,
+) -> io::Result<Option<Vec<String>>> {
+    let mut operands = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        // `--` ends the flags, so that files starting with `-` can be named
+        if arg == "--" {
+            operands.extend(args);
+            break;
+        }
+        let flags: Vec<(char, Option<&str>)> = if let Some(option) = arg.strip_prefix("--") {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (option, None),
+            };
+            let flag = match spec.long.iter().find(|(long, _)| *long == name) {
+                Some(&(_, flag)) => flag,
+                None => {
+                    writeln!(err, "{}: unrecognized option '{}'", command, arg)?;
+                    return Ok(None);
+                }
+            };
+            if value.is_some() && !spec.with_value.contains(&flag) {
+                writeln!(
+                    err,
+                    "{}: option '--{}' doesn't allow an argument",
+                    command, name
+                )?;
+                return Ok(None);
+            }
+            vec![(flag, value)]
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            arg[1..].chars().map(|flag| (flag, None)).collect()
+        } else {
+            operands.push(arg);
+            continue;
+        };
+
+        for (flag, value) in flags {
+            if !spec.short.contains(flag) {
+                writeln!(err, "{}: invalid option -- '{}'", command, flag)?;
+                return Ok(None);
+            }
+            if let Err(e) = apply(flag, value) {
+                writeln!(err, "{}: {}", command, e)?;
+                return Ok(None);
+            }
+        }
+    }
+    Ok(Some(operands))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: FlagSpec = FlagSpec {
+        short: "rRfm",
+        long: &[("recursive", 'r'), ("force", 'f'), ("mode", 'm')],
+        with_value: &['m'],
+    };
+
+    // The operands, flags (like `m=x` with a value) and error output of
+    // parsing `args`
+    fn parse(args: &[&str]) -> (Option<Vec<String>>, Vec<String>, String) {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        let mut flags = Vec::new();
+        let mut err = Vec::new();
+        let operands = parse_flags("test", args, &SPEC, &mut err, |flag, value| {
+            flags.push(match value {
+                Some(value) => format!("{}={}", flag, value),
+                None => flag.to_string(),
+            });
+            Ok(())
+        })
+        .unwrap();
+        (operands, flags, String::from_utf8(err).unwrap())
+    }
+
+    #[test]
+    fn combined_and_long_flags() {
+        let (operands, flags, _) = parse(&["-rf", "a", "--force", "b", "--mode=x"]);
+        assert_eq!(operands, Some(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(flags, ["r", "f", "f", "m=x"]);
+    }
+
+    #[test]
+    fn double_dash_ends_flags() {
+        let (operands, flags, _) = parse(&["-r", "--", "-f", "--force", "--"]);
+        let expected = ["-f", "--force", "--"].map(str::to_string).to_vec();
+        assert_eq!(operands, Some(expected));
+        assert_eq!(flags, ["r"]);
+    }
+
+    #[test]
+    fn lone_dash_is_an_operand() {
+        let (operands, _, _) = parse(&["-"]);
+        assert_eq!(operands, Some(vec!["-".to_string()]));
+    }
+
+    #[test]
+    fn mistakes() {
+        assert_eq!(parse(&["-x"]).2, "test: invalid option -- 'x'\n");
+        assert_eq!(
+            parse(&["--bogus"]).2,
+            "test: unrecognized option '--bogus'\n"
+        );
+        assert_eq!(
+            parse(&["--force=1"]).2,
+            "test: option '--force' doesn't allow an argument\n"
+        );
+        assert_eq!(parse(&["-rx"]).0, None);
+    }
+
+    #[test]
+    fn completions() {
+        let expected = ["-r", "-R", "-f", "-m", "--recursive", "--force", "--mode"];
+        assert_eq!(SPEC.completions(), expected.map(str::to_string).to_vec());
+    }
+}
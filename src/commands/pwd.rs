@@ -1,8 +1,15 @@
 use std::env;
+use std::io::{self, Write};
 
-pub fn pwd() {
+pub fn pwd(out: &mut dyn Write, err: &mut dyn Write) -> io::Result<i32> {
     match env::current_dir() {
-        Ok(path) => println!("{}", path.display()),
-        Err(e) => eprintln!("pwd: error retrieving current directory: {}", e),
+        Ok(path) => {
+            writeln!(out, "{}", path.display())?;
+            Ok(0)
+        }
+        Err(e) => {
+            writeln!(err, "pwd: error retrieving current directory: {}", e)?;
+            Ok(1)
+        }
     }
 }
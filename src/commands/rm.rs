@@ -1,86 +1,155 @@
-use std::fs;
+use super::options::{confirm, parse_flags, FlagSpec};
+use crate::interrupt;
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
 use std::path::Path;
-use std::io;
 
-pub fn rm(args: Vec<String>) {
-    if args.is_empty() {
-        println!("rm: missing operand");
-        println!("Usage: rm [-r] <file1> [file2 ...]");
-        return;
-    }
+// Flags accepted on the command line, also offered by tab completion
+pub const SPEC: FlagSpec = FlagSpec {
+    short: "rRfi",
+    long: &[("recursive", 'r'), ("force", 'f'), ("interactive", 'i')],
+    with_value: &[],
+};
 
-    let mut is_recursive = false;
-    let mut files = Vec::new();
+// What may be removed, and whether to ask
+#[derive(Default)]
+struct Options {
+    recursive: bool,
+    // Ignore missing files and never ask (`-f`)
+    force: bool,
+    // Ask before every removal (`-i`)
+    interactive: bool,
+}
 
-    // Loop through arguments to identify flags and files
-    for arg in args {
-        if &arg == "-r" {
-            is_recursive = true;
-        } else {
-            files.push(arg);
+pub fn rm(args: Vec<String>, err: &mut dyn Write) -> io::Result<i32> {
+    let mut options = Options::default();
+    // The last of -f and -i wins
+    let files = parse_flags("rm", args, &SPEC, err, |flag, _| {
+        match flag {
+            'r' | 'R' => options.recursive = true,
+            'f' => {
+                options.force = true;
+                options.interactive = false;
+            }
+            'i' => {
+                options.interactive = true;
+                options.force = false;
+            }
+            _ => {}
         }
-    }
-    
+        Ok(())
+    })?;
+    let files = match files {
+        Some(files) => files,
+        None => return Ok(1),
+    };
+
     if files.is_empty() {
-        println!("rm: missing operand");
-        println!("Usage: rm [-r] <file1> [file2 ...]");
-        return;
+        // Nothing to do is not an error with -f
+        if options.force {
+            return Ok(0);
+        }
+        writeln!(err, "rm: missing operand")?;
+        writeln!(err, "Usage: rm [-rfi] <file1> [file2 ...]")?;
+        return Ok(1);
     }
-    
+
+    let mut status = 0;
     for file in files {
-        if let Err(err) = remove_file(&file, is_recursive) {
-            println!("rm: {}", err);
+        interrupt::check()?;
+        if let Err(e) = remove_file(&file, &options) {
+            writeln!(err, "rm: {}", e)?;
+            status = 1;
         }
     }
+    Ok(status)
 }
 
-fn remove_file(file: &str, recursive: bool) -> io::Result<()> {
+fn remove_file(file: &str, options: &Options) -> io::Result<()> {
     let path = Path::new(file);
-    // Check if file exists
-    if !path.exists() {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("cannot remove '{}': No such file or directory", file)
-        ));
-    }
+    // Check if file exists, without following symbolic links
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) if options.force => return Ok(()),
+        Err(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("cannot remove '{}': No such file or directory", file),
+            ))
+        }
+    };
     // If it's a directory
-    if path.is_dir() {
-        if recursive {
+    if metadata.is_dir() {
+        if options.recursive {
             // With -r, remove the directory and its contents
-            remove_dir_recursive(path)
+            remove_dir_recursive(path, options).map(|_| ())
         } else {
             // Without -r, return an error
             Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                format!("cannot remove '{}': Is a directory", file)
+                format!("cannot remove '{}': Is a directory", file),
             ))
         }
     } else {
         // Remove a normal file
+        if options.interactive && !ask_remove(path, &metadata) {
+            return Ok(());
+        }
         fs::remove_file(path)
     }
 }
 
-fn remove_dir_recursive(path: &Path) -> io::Result<()> {
-    // If it's a directory, we must first remove its contents
-    if path.is_dir() {
-        // Loop through directory contents
-        for entry in fs::read_dir(path)? {
-            let entry = entry?;
-            let path = entry.path();
-            
-            if path.is_dir() {
-                // Recursive call for subdirectories
-                remove_dir_recursive(&path)?;
-            } else {
-                // Remove files
-                fs::remove_file(&path)?;
-            }
+// Remove a directory and its contents. Returns false when the user chose to
+// keep something, in which case the directory itself is kept too.
+fn remove_dir_recursive(path: &Path, options: &Options) -> io::Result<bool> {
+    if options.interactive
+        && !confirm(&format!(
+            "rm: descend into directory '{}'? ",
+            path.display()
+        ))
+    {
+        return Ok(false);
+    }
+
+    // We must first remove its contents. Symbolic links to directories are
+    // removed, never followed.
+    let mut emptied = true;
+    for entry in fs::read_dir(path)? {
+        interrupt::check()?;
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = fs::symlink_metadata(&path)?;
+
+        if metadata.is_dir() {
+            // Recursive call for subdirectories
+            emptied &= remove_dir_recursive(&path, options)?;
+        } else if !options.interactive || ask_remove(&path, &metadata) {
+            // Remove files
+            fs::remove_file(&path)?;
+        } else {
+            emptied = false;
         }
-        // Once the directory is empty, we can remove it
-        fs::remove_dir(path)
-    } else {
-        // If it's not a directory, remove it directly
-        fs::remove_file(path)
     }
-}
\ No newline at end of file
+
+    // Once the directory is empty, we can remove it
+    if !emptied
+        || options.interactive && !confirm(&format!("rm: remove directory '{}'? ", path.display()))
+    {
+        return Ok(false);
+    }
+    fs::remove_dir(path)?;
+    Ok(true)
+}
+
+fn ask_remove(path: &Path, metadata: &Metadata) -> bool {
+    let kind = if metadata.file_type().is_symlink() {
+        "symbolic link"
+    } else if metadata.is_file() && metadata.len() == 0 {
+        "regular empty file"
+    } else if metadata.is_file() {
+        "regular file"
+    } else {
+        "file"
+    };
+    confirm(&format!("rm: remove {} '{}'? ", kind, path.display()))
+}
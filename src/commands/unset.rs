@@ -0,0 +1,16 @@
+use crate::expand::is_valid_name;
+use std::env;
+use std::io::{self, Write};
+
+pub fn unset(args: Vec<String>, err: &mut dyn Write) -> io::Result<i32> {
+    let mut status = 0;
+    for name in args {
+        if !is_valid_name(&name) {
+            writeln!(err, "unset: `{}': not a valid identifier", name)?;
+            status = 1;
+            continue;
+        }
+        env::remove_var(&name);
+    }
+    Ok(status)
+}
@@ -0,0 +1,217 @@
+use crate::commands;
+use crate::executor::BUILTINS;
+use std::{collections::BTreeSet, env, fs, os::unix::fs::PermissionsExt, path::Path};
+
+// The possible completions of the word under the cursor
+pub struct Completion {
+    // Index in the line where the word being completed starts
+    pub start: usize,
+    // The word as typed so far, without its quotes
+    pub word: String,
+    // Full replacement words, sorted and without duplicates
+    pub candidates: Vec<String>,
+}
+
+// What the word under the cursor is, depending on the words before it
+enum Position {
+    Command,
+    Argument { command: String },
+    // The file name after `<` or `>`
+    Redirect,
+}
+
+// Complete the word ending at `cursor`: builtins and PATH programs for the
+// command name, flags for `-` arguments of builtins, file names otherwise
+// (directories only for `cd`)
+pub fn complete(line: &[char], cursor: usize) -> Completion {
+    let (start, word, position) = current_word(&line[..cursor]);
+
+    let candidates = match position {
+        Position::Command if !word.contains('/') => commands_starting_with(&word),
+        Position::Command => paths_starting_with(&word, false),
+        Position::Argument { command } if word.starts_with('-') => commands::flags(&command)
+            .into_iter()
+            .filter(|flag| flag.starts_with(&word))
+            .collect(),
+        Position::Argument { command } => paths_starting_with(&word, command == "cd"),
+        Position::Redirect => paths_starting_with(&word, false),
+    };
+
+    Completion {
+        start,
+        word,
+        candidates,
+    }
+}
+
+// The part of a candidate shown when listing several of them
+pub fn display(candidate: &str) -> &str {
+    let trimmed = candidate.trim_end_matches('/');
+    match trimmed.rfind('/') {
+        Some(i) => &candidate[i + 1..],
+        None => candidate,
+    }
+}
+
+// Find where the last word starts, its text without quotes, and whether it
+// is a command name or an argument
+fn current_word(line: &[char]) -> (usize, String, Position) {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut start = 0;
+    let mut redirect = false;
+    let mut quote: Option<char> = None;
+
+    for (i, &c) in line.iter().enumerate() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (_, Some(q)) if c == q => quote = None,
+            (_, Some(_)) => word.push(c),
+            (' ' | '\t' | '|' | ';' | '&' | '<' | '>', None) => {
+                if !word.is_empty() {
+                    // A redirection target is not an argument of the command
+                    if redirect {
+                        redirect = false;
+                    } else {
+                        words.push(word.clone());
+                    }
+                    word.clear();
+                }
+                match c {
+                    '|' | ';' | '&' => {
+                        words.clear();
+                        redirect = false;
+                    }
+                    '<' | '>' => redirect = true,
+                    _ => {}
+                }
+                start = i + 1;
+            }
+            _ => word.push(c),
+        }
+    }
+
+    let position = if redirect {
+        Position::Redirect
+    } else if words.is_empty() {
+        Position::Command
+    } else {
+        Position::Argument {
+            command: words[0].clone(),
+        }
+    };
+    (start, word, position)
+}
+
+fn commands_starting_with(prefix: &str) -> Vec<String> {
+    let mut names: BTreeSet<String> = BUILTINS
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| name.to_string())
+        .collect();
+
+    if let Some(paths) = env::var_os("PATH") {
+        for dir in env::split_paths(&paths) {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if !name.starts_with(prefix) || names.contains(&name) {
+                    continue;
+                }
+                let executable = fs::metadata(entry.path())
+                    .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0);
+                if executable {
+                    names.insert(name);
+                }
+            }
+        }
+    }
+
+    names.into_iter().collect()
+}
+
+// Complete a file name; directories get a trailing `/` so that completion
+// can go on inside them
+fn paths_starting_with(word: &str, directories_only: bool) -> Vec<String> {
+    let (dir, prefix) = match word.rfind('/') {
+        Some(i) => (&word[..=i], &word[i + 1..]),
+        None => ("", word),
+    };
+
+    // `~/` is looked up in the home directory but kept as typed
+    let lookup = match (dir.strip_prefix("~/"), env::var("HOME")) {
+        (Some(rest), Ok(home)) => format!("{}/{}", home, rest),
+        _ if dir.is_empty() => ".".to_string(),
+        _ => dir.to_string(),
+    };
+
+    let entries = match fs::read_dir(&lookup) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Hidden files only when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = Path::new(&lookup).join(&name).is_dir();
+            if directories_only && !is_dir {
+                return None;
+            }
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+// Longest prefix shared by all candidates
+pub fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix: Vec<char> = match candidates.first() {
+        Some(first) => first.chars().collect(),
+        None => return String::new(),
+    };
+    for candidate in &candidates[1..] {
+        let len = prefix
+            .iter()
+            .zip(candidate.chars())
+            .take_while(|(a, b)| **a == *b)
+            .count();
+        prefix.truncate(len);
+    }
+    prefix.into_iter().collect()
+}
+
+// Quote a completed word if the tokenizer would otherwise split or expand
+// it. Nothing is special between single quotes, so those are used unless the
+// word contains one. A leading `~/` stays unquoted to still be expanded.
+pub fn quote(word: &str, complete: bool) -> String {
+    let (home, rest) = match word.strip_prefix("~/") {
+        Some(rest) => ("~/", rest),
+        None => ("", word),
+    };
+    let special = |c: char| c.is_whitespace() || "|;&<>'\"$*?[".contains(c);
+    if !rest.contains(special) {
+        return word.to_string();
+    }
+    let quote = if !rest.contains('\'') {
+        '\''
+    } else if !rest.contains(['"', '$']) {
+        '"'
+    } else {
+        // Such names cannot be quoted and are left as they are
+        return word.to_string();
+    };
+    if complete {
+        format!("{}{}{}{}", home, quote, rest, quote)
+    } else {
+        format!("{}{}{}", home, quote, rest)
+    }
+}
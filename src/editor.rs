@@ -0,0 +1,452 @@
+use crate::completion::{self, Completion};
+use std::{
+    io::{self, BufRead, Write},
+    mem::MaybeUninit,
+};
+
+// A key press, decoded from the raw bytes sent by the terminal
+enum Key {
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    WordLeft,
+    WordRight,
+    Ctrl(u8),
+    Unknown,
+}
+
+// Puts the terminal in raw mode and restores the previous settings when dropped
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> io::Result<RawMode> {
+        let mut original = MaybeUninit::<libc::termios>::uninit();
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, original.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let original = unsafe { original.assume_init() };
+
+        // Read bytes one by one without echo; Ctrl-C and Ctrl-Z arrive as keys
+        let mut raw = original;
+        raw.c_iflag &= !(libc::ICRNL | libc::IXON | libc::BRKINT | libc::INPCK | libc::ISTRIP);
+        raw.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG | libc::IEXTEN);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(RawMode { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original) };
+    }
+}
+
+// The line being edited
+struct Line {
+    prompt: String,
+    buffer: Vec<char>,
+    // Cursor position, as an index into `buffer`
+    cursor: usize,
+}
+
+// How a Ctrl-R search ended
+enum Search {
+    // Enter: run the line found
+    Run,
+    // Any other key: keep editing, starting with that key
+    Edit(Option<Key>),
+}
+
+// Read a line, returning `None` at end of input. On a terminal the line can be
+// edited, and up/down browse `history`.
+pub fn read_line(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        return read_plain_line(prompt);
+    }
+
+    let raw_mode = RawMode::enable()?;
+    let result = edit(prompt, history);
+    drop(raw_mode);
+    result
+}
+
+fn edit(prompt: &str, history: &[String]) -> io::Result<Option<String>> {
+    let mut line = Line {
+        prompt: prompt.to_string(),
+        buffer: Vec::new(),
+        cursor: 0,
+    };
+    // Position in the history; `history.len()` is the line being typed
+    let mut index = history.len();
+    // What was typed before browsing the history, restored when coming back
+    let mut draft: Vec<char> = Vec::new();
+
+    // A key left over from a Ctrl-R search
+    let mut pending: Option<Key> = None;
+    // A second Tab in a row lists the candidates
+    let mut tabbed = false;
+
+    line.refresh()?;
+    loop {
+        let key = match pending.take() {
+            Some(key) => key,
+            None => read_key()?,
+        };
+        if let Key::Tab = key {
+            tabbed = line.complete(tabbed)?;
+            line.refresh()?;
+            continue;
+        }
+        tabbed = false;
+
+        match key {
+            Key::Enter => {
+                print!("\r\n");
+                io::stdout().flush()?;
+                return Ok(Some(line.buffer.iter().collect()));
+            }
+            Key::Char(c) => line.insert(c),
+            Key::Backspace => line.backspace(),
+            Key::Delete => line.delete(),
+            Key::Left => line.cursor = line.cursor.saturating_sub(1),
+            Key::Right => line.cursor = (line.cursor + 1).min(line.buffer.len()),
+            Key::Home | Key::Ctrl(b'a') => line.cursor = 0,
+            Key::End | Key::Ctrl(b'e') => line.cursor = line.buffer.len(),
+            Key::WordLeft => line.cursor = line.previous_word(),
+            Key::WordRight => line.cursor = line.next_word(),
+            Key::Up | Key::Ctrl(b'p') => {
+                if index > 0 {
+                    if index == history.len() {
+                        draft = line.buffer.clone();
+                    }
+                    index -= 1;
+                    line.set(history[index].chars().collect());
+                }
+            }
+            Key::Down | Key::Ctrl(b'n') => {
+                if index < history.len() {
+                    index += 1;
+                    if index == history.len() {
+                        line.set(draft.clone());
+                    } else {
+                        line.set(history[index].chars().collect());
+                    }
+                }
+            }
+            Key::Ctrl(b'b') => line.cursor = line.cursor.saturating_sub(1),
+            Key::Ctrl(b'f') => line.cursor = (line.cursor + 1).min(line.buffer.len()),
+            // Ctrl-C abandons the line
+            Key::Ctrl(b'c') => {
+                print!("^C\r\n");
+                io::stdout().flush()?;
+                return Ok(Some(String::new()));
+            }
+            // Ctrl-D ends the input on an empty line and deletes otherwise
+            Key::Ctrl(b'd') => {
+                if line.buffer.is_empty() {
+                    print!("\r\n");
+                    io::stdout().flush()?;
+                    return Ok(None);
+                }
+                line.delete();
+            }
+            Key::Ctrl(b'k') => line.buffer.truncate(line.cursor),
+            Key::Ctrl(b'u') => {
+                line.buffer.drain(..line.cursor);
+                line.cursor = 0;
+            }
+            Key::Ctrl(b'w') => {
+                let start = line.previous_word();
+                line.buffer.drain(start..line.cursor);
+                line.cursor = start;
+            }
+            Key::Ctrl(b'l') => print!("\x1b[H\x1b[2J"),
+            Key::Ctrl(b'r') => match line.search(history)? {
+                Search::Run => {
+                    print!("\r\n");
+                    io::stdout().flush()?;
+                    return Ok(Some(line.buffer.iter().collect()));
+                }
+                Search::Edit(key) => pending = key,
+            },
+            Key::Tab | Key::Ctrl(_) | Key::Unknown => {}
+        }
+        line.refresh()?;
+    }
+}
+
+impl Line {
+    fn insert(&mut self, c: char) {
+        self.buffer.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.buffer.len() {
+            self.buffer.remove(self.cursor);
+        }
+    }
+
+    fn set(&mut self, buffer: Vec<char>) {
+        self.cursor = buffer.len();
+        self.buffer = buffer;
+    }
+
+    // Start of the word before the cursor, skipping the spaces in between
+    fn previous_word(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && !self.buffer[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        while i > 0 && self.buffer[i - 1].is_alphanumeric() {
+            i -= 1;
+        }
+        i
+    }
+
+    // End of the word after the cursor
+    fn next_word(&self) -> usize {
+        let mut i = self.cursor;
+        while i < self.buffer.len() && !self.buffer[i].is_alphanumeric() {
+            i += 1;
+        }
+        while i < self.buffer.len() && self.buffer[i].is_alphanumeric() {
+            i += 1;
+        }
+        i
+    }
+
+    // Complete the word under the cursor. Returns whether the candidates
+    // should be listed on the next Tab, i.e. when nothing could be inserted.
+    fn complete(&mut self, tabbed: bool) -> io::Result<bool> {
+        let Completion {
+            start,
+            word,
+            candidates,
+        } = completion::complete(&self.buffer, self.cursor);
+
+        match candidates.as_slice() {
+            [] => Ok(false),
+            [candidate] => {
+                // Finished words get a space, directories can be completed further
+                let text = if let Some(dir) = candidate.strip_suffix('/') {
+                    completion::quote(dir, true) + "/"
+                } else {
+                    completion::quote(candidate, true) + " "
+                };
+                self.replace(start, &text);
+                Ok(false)
+            }
+            candidates => {
+                let prefix = completion::common_prefix(candidates);
+                if prefix.chars().count() > word.chars().count() {
+                    self.replace(start, &completion::quote(&prefix, false));
+                    return Ok(false);
+                }
+                if tabbed {
+                    let names: Vec<&str> =
+                        candidates.iter().map(|c| completion::display(c)).collect();
+                    print!("\r\n{}\r\n", names.join("  "));
+                }
+                Ok(true)
+            }
+        }
+    }
+
+    // Replace the text between `start` and the cursor
+    fn replace(&mut self, start: usize, text: &str) {
+        let chars: Vec<char> = text.chars().collect();
+        let len = chars.len();
+        self.buffer.splice(start..self.cursor, chars);
+        self.cursor = start + len;
+    }
+
+    // Incremental reverse search through the history: each typed character
+    // narrows the search, Ctrl-R jumps to the next older match
+    fn search(&mut self, history: &[String]) -> io::Result<Search> {
+        let original = (self.buffer.clone(), self.cursor);
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        let mut failed = false;
+
+        loop {
+            let matched = found.map_or("", |i| history[i].as_str());
+            let status = if failed {
+                "failed reverse-i-search"
+            } else {
+                "reverse-i-search"
+            };
+            print!("\r({})`{}': {}\x1b[K", status, query, matched);
+            io::stdout().flush()?;
+
+            // Search from the current match for a longer query, before it for Ctrl-R
+            let (from, key) = match read_key()? {
+                Key::Char(c) => {
+                    query.push(c);
+                    (found.map_or(history.len(), |i| i + 1), None)
+                }
+                Key::Backspace => {
+                    query.pop();
+                    (history.len(), None)
+                }
+                Key::Ctrl(b'r') => (found.unwrap_or(history.len()), None),
+                // Ctrl-C and Ctrl-G give the original line back
+                Key::Ctrl(b'c') | Key::Ctrl(b'g') => {
+                    (self.buffer, self.cursor) = original;
+                    return Ok(Search::Edit(None));
+                }
+                key => (0, Some(key)),
+            };
+
+            if let Some(key) = key {
+                if let Some(i) = found {
+                    self.set(history[i].chars().collect());
+                }
+                return Ok(match key {
+                    Key::Enter => Search::Run,
+                    key => Search::Edit(Some(key)),
+                });
+            }
+
+            match (0..from).rev().find(|&i| history[i].contains(&query)) {
+                Some(i) => {
+                    found = Some(i);
+                    failed = false;
+                }
+                None => failed = !query.is_empty(),
+            }
+        }
+    }
+
+    // Redraw the prompt and the line, then put the cursor back in place
+    fn refresh(&self) -> io::Result<()> {
+        let text: String = self.buffer.iter().collect();
+        let mut output = format!("\r{}{}\x1b[K", self.prompt, text);
+        let back = self.buffer.len() - self.cursor;
+        if back > 0 {
+            output.push_str(&format!("\x1b[{}D", back));
+        }
+        let mut stdout = io::stdout();
+        stdout.write_all(output.as_bytes())?;
+        stdout.flush()
+    }
+}
+
+// Used when the input is a file or a pipe: no editing, just lines
+fn read_plain_line(prompt: &str) -> io::Result<Option<String>> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    if io::stdin().lock().read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(input.trim_end_matches(['\n', '\r']).to_string()))
+}
+
+fn read_byte() -> io::Result<u8> {
+    let mut byte = 0u8;
+    loop {
+        let n = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+        match n {
+            1 => return Ok(byte),
+            // End of input behaves like Ctrl-D
+            0 => return Ok(4),
+            _ => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+fn read_key() -> io::Result<Key> {
+    let byte = read_byte()?;
+    Ok(match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        127 | 8 => Key::Backspace,
+        0x1b => read_escape()?,
+        1..=26 => Key::Ctrl(byte + b'a' - 1),
+        0..=31 => Key::Unknown,
+        _ => read_char(byte)?,
+    })
+}
+
+// Decode a UTF-8 character from its first byte and the continuation bytes
+fn read_char(first: u8) -> io::Result<Key> {
+    let len = match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    };
+    let mut bytes = vec![first];
+    for _ in 1..len {
+        bytes.push(read_byte()?);
+    }
+    let c = std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|s| s.chars().next());
+    Ok(c.map_or(Key::Unknown, Key::Char))
+}
+
+// Decode the escape sequences sent for arrows, Home/End, Delete and word jumps
+fn read_escape() -> io::Result<Key> {
+    Ok(match read_byte()? {
+        b'b' => Key::WordLeft,
+        b'f' => Key::WordRight,
+        b'O' => match read_byte()? {
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            _ => Key::Unknown,
+        },
+        b'[' => {
+            // Parameters such as `1;5` in `ESC [1;5C` (Ctrl-Right)
+            let mut params = String::new();
+            let mut last = read_byte()?;
+            while last.is_ascii_digit() || last == b';' {
+                params.push(last as char);
+                last = read_byte()?;
+            }
+            let modified = params.ends_with(";5") || params.ends_with(";3");
+            match (last, params.as_str()) {
+                (b'A', _) => Key::Up,
+                (b'B', _) => Key::Down,
+                (b'C', _) if modified => Key::WordRight,
+                (b'D', _) if modified => Key::WordLeft,
+                (b'C', _) => Key::Right,
+                (b'D', _) => Key::Left,
+                (b'H', _) | (b'~', "1") | (b'~', "7") => Key::Home,
+                (b'F', _) | (b'~', "4") | (b'~', "8") => Key::End,
+                (b'~', "3") => Key::Delete,
+                _ => Key::Unknown,
+            }
+        }
+        _ => Key::Unknown,
+    })
+}
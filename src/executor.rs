@@ -0,0 +1,259 @@
+use crate::commands::{cat, cd, cp, echo, env, export, history, ls, mkdir, mv, pwd, rm, unset};
+use crate::expand::expand;
+use crate::external;
+use crate::history::History;
+use crate::interrupt;
+use crate::parser::{Command, Connector, List, Pipeline};
+use crate::redirect::{Input, Streams};
+use std::{
+    io::{self, Write},
+    os::unix::io::RawFd,
+};
+
+// What the prompt loop should do after a line has been executed
+pub enum Flow {
+    Continue,
+    Exit(i32),
+}
+
+// Commands implemented by the shell itself; anything else is looked up in PATH
+pub const BUILTINS: [&str; 14] = [
+    "exit", "echo", "cd", "ls", "pwd", "cat", "cp", "rm", "mv", "mkdir", "export", "unset", "env",
+    "history",
+];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.contains(&name)
+}
+
+// State kept by the shell between two command lines
+pub struct Shell {
+    // Exit status of the last command, exposed as `$?`
+    pub status: i32,
+    pub history: History,
+}
+
+impl Shell {
+    pub fn new() -> Shell {
+        Shell {
+            status: 0,
+            history: History::load(),
+        }
+    }
+
+    pub fn execute(&mut self, list: &List) -> Flow {
+        for (connector, pipeline) in list {
+            // Skipped pipelines leave `$?` untouched, so `a && b || c` runs `c`
+            // when either `a` or `b` fails
+            let skip = match connector {
+                Connector::Sequence => false,
+                Connector::And => self.status != 0,
+                Connector::Or => self.status == 0,
+            };
+            if skip {
+                continue;
+            }
+            if let Flow::Exit(code) = self.execute_pipeline(pipeline) {
+                return Flow::Exit(code);
+            }
+        }
+        Flow::Continue
+    }
+
+    fn execute_pipeline(&mut self, pipeline: &Pipeline) -> Flow {
+        let commands: Vec<Command<String>> = match pipeline
+            .iter()
+            .map(|command| expand(command, self.status))
+            .collect()
+        {
+            Ok(commands) => commands,
+            Err(e) => {
+                eprintln!("0-shell: {}", e);
+                self.status = 1;
+                return Flow::Continue;
+            }
+        };
+
+        self.status = match commands.as_slice() {
+            [] => return Flow::Continue,
+            // A lone command runs inside the shell so that `cd` and `exit` take effect
+            [command] => match command.words.first().map(String::as_str) {
+                None => 0,
+                Some("exit") => match exit_status(&command.words[1..], self.status) {
+                    Ok(code) => return Flow::Exit(code),
+                    Err(e) => {
+                        eprintln!("exit: {}", e);
+                        1
+                    }
+                },
+                Some(name) if is_builtin(name) => self.run_builtin(command, Input::Stdin),
+                Some(_) => run_external(command).unwrap_or_else(|e| {
+                    eprintln!("0-shell: {}", e);
+                    1
+                }),
+            },
+            commands => self.run_pipeline(commands).unwrap_or_else(|e| {
+                eprintln!("0-shell: {}", e);
+                1
+            }),
+        };
+
+        Flow::Continue
+    }
+
+    fn run_builtin(&self, command: &Command<String>, input: Input) -> i32 {
+        let mut streams = match Streams::new(input).redirect(&command.redirects) {
+            Ok(streams) => streams,
+            Err(e) => {
+                eprintln!("0-shell: {}", e);
+                return 1;
+            }
+        };
+        let Streams { input, out, err } = &mut streams;
+
+        let name = command.words[0].as_str();
+        let args = command.words[1..].to_vec();
+        interrupt::clear();
+        let result = match name {
+            "echo" => echo(args, out),
+            "cd" => cd(args, err),
+            "ls" => ls(args, out.terminal_width(), out, err),
+            "pwd" => pwd(out, err),
+            "cat" => cat(args, input, out, err),
+            "cp" => cp(args, err),
+            "rm" => rm(args, err),
+            "mv" => mv(args, err),
+            "mkdir" => mkdir(args, err),
+            "export" => export(args, out, err),
+            "unset" => unset(args, err),
+            "env" => env(args, out, err),
+            "history" => history(args, self.history.entries(), out, err),
+            _ => unreachable!("{} is not a builtin", name),
+        };
+
+        // Stopped by Ctrl-C: like an interrupted program, leave the prompt
+        // on a fresh line
+        if interrupt::is_set() {
+            let _ = out.flush();
+            eprintln!();
+            return 128 + libc::SIGINT;
+        }
+
+        match result.and_then(|status| out.flush().map(|_| status)) {
+            Ok(status) => status,
+            Err(e) => {
+                let _ = writeln!(err, "{}: write error: {}", name, e);
+                1
+            }
+        }
+    }
+
+    // Fork one process per stage, wiring each stdout to the next stdin with a pipe.
+    // The status of a pipeline is the status of its last command.
+    fn run_pipeline(&self, commands: &[Command<String>]) -> io::Result<i32> {
+        let mut children = Vec::new();
+        let mut prev_read: Option<RawFd> = None;
+
+        // Anything still buffered would otherwise be written once per child
+        io::stdout().flush()?;
+
+        for (i, command) in commands.iter().enumerate() {
+            let pipe = if i + 1 < commands.len() {
+                Some(create_pipe()?)
+            } else {
+                None
+            };
+
+            let pid = unsafe { libc::fork() };
+            if pid < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            if pid == 0 {
+                unsafe {
+                    if let Some(fd) = prev_read {
+                        libc::dup2(fd, libc::STDIN_FILENO);
+                        libc::close(fd);
+                    }
+                    if let Some((read, write)) = pipe {
+                        libc::dup2(write, libc::STDOUT_FILENO);
+                        libc::close(read);
+                        libc::close(write);
+                    }
+                }
+
+                let code = match command.words.first().map(String::as_str) {
+                    None => 0,
+                    Some("exit") => {
+                        exit_status(&command.words[1..], self.status).unwrap_or_else(|e| {
+                            eprintln!("exit: {}", e);
+                            1
+                        })
+                    }
+                    Some(name) if is_builtin(name) => {
+                        // Read fd 0 directly: the shell's own stdin buffer belongs to the prompt
+                        external::reset_signals();
+                        self.run_builtin(command, Input::from_fd(libc::STDIN_FILENO))
+                    }
+                    Some(_) => external::exec(command),
+                };
+                let _ = io::stdout().flush();
+                unsafe { libc::_exit(code) };
+            }
+
+            children.push(pid);
+
+            // The parent keeps only the read end needed by the next stage
+            unsafe {
+                if let Some(fd) = prev_read {
+                    libc::close(fd);
+                }
+                if let Some((read, write)) = pipe {
+                    libc::close(write);
+                    prev_read = Some(read);
+                }
+            }
+        }
+
+        let mut status = 0;
+        for pid in children {
+            status = external::wait(pid);
+        }
+
+        Ok(status)
+    }
+}
+
+// `exit` uses the last status unless given one; only the low byte reaches the parent
+fn exit_status(args: &[String], status: i32) -> Result<i32, String> {
+    match args {
+        [] => Ok(status),
+        [code] => code
+            .parse::<i64>()
+            .map(|code| (code & 0xff) as i32)
+            .map_err(|_| format!("{}: numeric argument required", code)),
+        _ => Err("too many arguments".to_string()),
+    }
+}
+
+fn run_external(command: &Command<String>) -> io::Result<i32> {
+    io::stdout().flush()?;
+
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if pid == 0 {
+        external::exec(command);
+    }
+
+    Ok(external::wait(pid))
+}
+
+fn create_pipe() -> io::Result<(RawFd, RawFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((fds[0], fds[1]))
+}
@@ -0,0 +1,288 @@
+use crate::glob;
+use crate::parser::{Command, Redirect, Word, WordPart};
+use std::{env, iter::Peekable, str::Chars};
+
+// Turn the words of a parsed command into the strings passed to the program
+pub fn expand(command: &Command, status: i32) -> Result<Command<String>, String> {
+    let mut words = Vec::new();
+    for word in &command.words {
+        words.extend(expand_word(word, status));
+    }
+
+    let mut redirects = Vec::new();
+    for redirect in &command.redirects {
+        redirects.push(match redirect {
+            Redirect::Input(path) => Redirect::Input(expand_target(path, status)?),
+            Redirect::Output { fd, path, append } => Redirect::Output {
+                fd: *fd,
+                path: expand_target(path, status)?,
+                append: *append,
+            },
+            Redirect::Duplicate { fd, to } => Redirect::Duplicate { fd: *fd, to: *to },
+        });
+    }
+
+    Ok(Command { words, redirects })
+}
+
+// A redirection needs exactly one file name after expansion
+fn expand_target(word: &Word, status: i32) -> Result<String, String> {
+    let mut fields = expand_word(word, status);
+    if fields.len() != 1 {
+        return Err(format!("{}: ambiguous redirect", word));
+    }
+    Ok(fields.remove(0))
+}
+
+// A field being built, with the glob pattern it stands for: quoted
+// characters are escaped in the pattern so that they match literally
+#[derive(Default)]
+struct Field {
+    text: String,
+    pattern: String,
+    // Whether the field contains an unquoted wildcard
+    glob: bool,
+    // Whether the field exists even if empty, e.g. after ""
+    started: bool,
+}
+
+impl Field {
+    fn push_quoted(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\\' || glob::SPECIAL.contains(&c) {
+                self.pattern.push('\\');
+            }
+            self.pattern.push(c);
+        }
+        self.text.push_str(text);
+        self.started = true;
+    }
+
+    fn push_unquoted(&mut self, c: char) {
+        self.glob |= glob::SPECIAL.contains(&c);
+        self.text.push(c);
+        self.pattern.push(c);
+        self.started = true;
+    }
+
+    // Patterns matching nothing are left as typed, like bash does
+    fn finish(self, fields: &mut Vec<String>) {
+        if !self.started {
+            return;
+        }
+        let matches = if self.glob {
+            glob::glob(&self.pattern)
+        } else {
+            Vec::new()
+        };
+        if matches.is_empty() {
+            fields.push(self.text);
+        } else {
+            fields.extend(matches);
+        }
+    }
+}
+
+// Expand the variables and wildcards of a word. Unquoted expansions are split
+// on whitespace and may produce zero or several fields, quoted ones never do.
+fn expand_word(word: &Word, status: i32) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = Field::default();
+
+    for (i, part) in word.0.iter().enumerate() {
+        match part {
+            // Nothing is expanded between single quotes
+            WordPart::SingleQuoted(text) => current.push_quoted(text),
+            WordPart::DoubleQuoted(text) => current.push_quoted(&expand_variables(text, status)),
+            WordPart::Unquoted(text) => {
+                let text = if i == 0 {
+                    expand_tilde(text, word.0.len() == 1, &mut current)
+                } else {
+                    text
+                };
+                let mut chars = text.chars().peekable();
+                while let Some(c) = chars.next() {
+                    if c != '$' {
+                        current.push_unquoted(c);
+                        continue;
+                    }
+                    let value = match variable(&mut chars, status) {
+                        Some(value) => value,
+                        None => {
+                            current.push_unquoted('$');
+                            continue;
+                        }
+                    };
+                    for c in value.chars() {
+                        if c.is_whitespace() {
+                            std::mem::take(&mut current).finish(&mut fields);
+                        } else {
+                            current.push_unquoted(c);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    current.finish(&mut fields);
+    fields
+}
+
+// A word starting with an unquoted `~/`, or that is just `~`, starts in the
+// home directory. The home directory goes into the field as if quoted, and
+// the rest of the text is returned.
+fn expand_tilde<'a>(text: &'a str, whole_word: bool, current: &mut Field) -> &'a str {
+    let rest = match text.strip_prefix('~') {
+        Some(rest) if rest.starts_with('/') || (rest.is_empty() && whole_word) => rest,
+        _ => return text,
+    };
+    match env::var("HOME") {
+        Ok(home) => {
+            current.push_quoted(&home);
+            rest
+        }
+        Err(_) => text,
+    }
+}
+
+// Expand every `$NAME`, `${NAME}`, `$?` and `$$` of a text, without splitting
+fn expand_variables(text: &str, status: i32) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '$' {
+            match variable(&mut chars, status) {
+                Some(value) => result.push_str(&value),
+                None => result.push('$'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// Read the variable reference following a `$` and return its value, unset
+// variables being empty. Returns `None` when the `$` is just a dollar sign.
+fn variable(chars: &mut Peekable<Chars>, status: i32) -> Option<String> {
+    match chars.peek()? {
+        '?' => {
+            chars.next();
+            Some(status.to_string())
+        }
+        '$' => {
+            chars.next();
+            Some(std::process::id().to_string())
+        }
+        '{' => {
+            let rest: String = chars.clone().skip(1).collect();
+            let name = &rest[..rest.find('}')?];
+            if !is_valid_name(name) {
+                return None;
+            }
+            // Skip `{`, the name (ASCII once validated) and `}`
+            chars.nth(name.len() + 1);
+            Some(env::var(name).unwrap_or_default())
+        }
+        c if c.is_ascii_alphabetic() || *c == '_' => {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            Some(env::var(&name).unwrap_or_default())
+        }
+        _ => None,
+    }
+}
+
+// Names accepted by `${NAME}`, `export` and `unset`
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    // The arguments a command line expands to, with `$?` being 3
+    fn words(input: &str) -> Vec<String> {
+        let list = parse(input).unwrap();
+        expand(&list[0].1[0], 3).unwrap().words
+    }
+
+    #[test]
+    fn unquoted_variables_are_split() {
+        env::set_var("EXPAND_TEST_SPLIT", " a  b\tc ");
+        assert_eq!(words("echo $EXPAND_TEST_SPLIT"), ["echo", "a", "b", "c"]);
+        assert_eq!(
+            words("echo x$EXPAND_TEST_SPLIT"),
+            ["echo", "x", "a", "b", "c"]
+        );
+        assert_eq!(words("echo \"$EXPAND_TEST_SPLIT\""), ["echo", " a  b\tc "]);
+        assert_eq!(
+            words("echo $EXPAND_TEST_SPLIT'd'"),
+            ["echo", "a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn empty_fields() {
+        env::remove_var("EXPAND_TEST_UNSET");
+        assert_eq!(words("echo $EXPAND_TEST_UNSET x"), ["echo", "x"]);
+        assert_eq!(words("echo \"$EXPAND_TEST_UNSET\" x"), ["echo", "", "x"]);
+        assert_eq!(words("echo \"\" ''"), ["echo", "", ""]);
+    }
+
+    #[test]
+    fn references() {
+        env::set_var("EXPAND_TEST_NAME", "value");
+        assert_eq!(words("echo ${EXPAND_TEST_NAME}s"), ["echo", "values"]);
+        assert_eq!(words("echo $EXPAND_TEST_NAME-x"), ["echo", "value-x"]);
+        assert_eq!(
+            words("echo '$EXPAND_TEST_NAME'"),
+            ["echo", "$EXPAND_TEST_NAME"]
+        );
+        assert_eq!(words("echo $? \"$?\""), ["echo", "3", "3"]);
+        assert_eq!(
+            words("echo $ a$ $1 ${1}"),
+            ["echo", "$", "a$", "$1", "${1}"]
+        );
+        assert_eq!(
+            words("echo ${EXPAND_TEST_NAME"),
+            ["echo", "${EXPAND_TEST_NAME"]
+        );
+    }
+
+    #[test]
+    fn tilde() {
+        let home = env::var("HOME").unwrap();
+        assert_eq!(words("cd ~"), ["cd", &home]);
+        assert_eq!(words("cd ~/a"), ["cd", &format!("{}/a", home)]);
+        assert_eq!(
+            words("echo ~x a~ '~' \"~\"/ ~'/'"),
+            ["echo", "~x", "a~", "~", "~/", "~/"]
+        );
+    }
+
+    #[test]
+    fn redirection_targets() {
+        env::set_var("EXPAND_TEST_FILES", "a b");
+        let list = parse("echo > $EXPAND_TEST_FILES").unwrap();
+        assert_eq!(
+            expand(&list[0].1[0], 0).unwrap_err(),
+            "$EXPAND_TEST_FILES: ambiguous redirect"
+        );
+    }
+}
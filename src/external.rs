@@ -0,0 +1,122 @@
+use crate::parser::Command;
+use crate::redirect;
+use std::{
+    env,
+    ffi::{CStr, CString},
+    fs,
+    io::{self, Write},
+    os::unix::{ffi::OsStrExt, fs::PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+// Exit codes used by POSIX shells when a program cannot be run
+const NOT_FOUND: i32 = 127;
+const NOT_EXECUTABLE: i32 = 126;
+
+// Look a program up in `PATH`, or use it as-is when it contains a slash
+pub fn find_program(name: &str) -> Option<PathBuf> {
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return path.exists().then_some(path);
+    }
+
+    let paths = env::var_os("PATH")?;
+    env::split_paths(&paths)
+        .map(|dir| dir.join(name))
+        .find(|path| is_executable(path))
+}
+
+fn is_executable(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+// Replace the current (forked) process with the program; only returns by exiting
+pub fn exec(command: &Command<String>) -> ! {
+    reset_signals();
+
+    if let Err(e) = redirect::apply_to_fds(&command.redirects) {
+        eprintln!("0-shell: {}", e);
+        exit(1);
+    }
+
+    let name = &command.words[0];
+    let path = match find_program(name) {
+        Some(path) => path,
+        None => {
+            eprintln!("Command '{}' not found.", name);
+            exit(NOT_FOUND);
+        }
+    };
+
+    // Arguments coming from the line editor cannot contain NUL bytes
+    let argv: Vec<CString> = command
+        .words
+        .iter()
+        .map(|arg| CString::new(arg.as_bytes()).unwrap_or_default())
+        .collect();
+    let mut argv_ptrs: Vec<*const libc::c_char> = argv.iter().map(|arg| arg.as_ptr()).collect();
+    argv_ptrs.push(std::ptr::null());
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).unwrap_or_default();
+    unsafe { libc::execv(c_path.as_ptr(), argv_ptrs.as_ptr()) };
+
+    // execv only returns on failure
+    let err = io::Error::last_os_error();
+    eprintln!("0-shell: {}: {}", name, err);
+    exit(if err.kind() == io::ErrorKind::NotFound {
+        NOT_FOUND
+    } else {
+        NOT_EXECUTABLE
+    });
+}
+
+// The shell ignores some signals; programs it starts should get the defaults back
+pub fn reset_signals() {
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+        libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+// Wait for a child and turn its wait status into a shell exit status,
+// telling the user when it was killed by a signal
+pub fn wait(pid: libc::pid_t) -> i32 {
+    let mut status = 0;
+    loop {
+        if unsafe { libc::waitpid(pid, &mut status, 0) } >= 0 {
+            break;
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            return 1;
+        }
+    }
+
+    if libc::WIFEXITED(status) {
+        return libc::WEXITSTATUS(status);
+    }
+
+    let signal = libc::WTERMSIG(status);
+    match signal {
+        // Interrupted programs just leave the prompt on a fresh line
+        libc::SIGINT => eprintln!(),
+        libc::SIGPIPE => {}
+        _ => {
+            let description = unsafe { CStr::from_ptr(libc::strsignal(signal)) };
+            if libc::WCOREDUMP(status) {
+                eprintln!("{} (core dumped)", description.to_string_lossy());
+            } else {
+                eprintln!("{}", description.to_string_lossy());
+            }
+        }
+    }
+    128 + signal
+}
+
+fn exit(code: i32) -> ! {
+    let _ = io::stderr().flush();
+    unsafe { libc::_exit(code) }
+}
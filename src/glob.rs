@@ -0,0 +1,224 @@
+use std::fs;
+
+// Characters that make an unquoted word a pattern
+pub const SPECIAL: [char; 3] = ['*', '?', '['];
+
+// Expand a pattern against the filesystem, returning the sorted matches.
+// Quoted characters arrive escaped with a backslash and only match themselves.
+pub fn glob(pattern: &str) -> Vec<String> {
+    let absolute = pattern.starts_with('/');
+    let components: Vec<&str> = pattern.trim_start_matches('/').split('/').collect();
+    let mut paths = vec![if absolute {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+
+        // A trailing slash only keeps the directories matched so far
+        if last && component.is_empty() && i > 0 {
+            return paths
+                .into_iter()
+                .filter(|path| fs::metadata(path).is_ok_and(|m| m.is_dir()))
+                .map(|path| format!("{}/", path))
+                .collect();
+        }
+
+        let mut next = Vec::new();
+        for path in &paths {
+            if !has_special(component) {
+                let path = join(path, &unescape(component));
+                // Intermediate directories are checked when read, the final one here
+                if !last || fs::symlink_metadata(&path).is_ok() {
+                    next.push(path);
+                }
+                continue;
+            }
+
+            let dir = if path.is_empty() { "." } else { path.as_str() };
+            let entries = match fs::read_dir(dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            let mut names: Vec<String> = entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| name_matches(component, name))
+                .collect();
+            names.sort();
+            next.extend(names.iter().map(|name| join(path, name)));
+        }
+        paths = next;
+    }
+
+    paths
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_string()
+    } else if path.ends_with('/') {
+        format!("{}{}", path, name)
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
+
+// Whether a pattern contains an unescaped wildcard
+fn has_special(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if SPECIAL.contains(&c) {
+            return true;
+        }
+    }
+    false
+}
+
+fn unescape(pattern: &str) -> String {
+    let mut result = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+// Match a file name against one pattern component. Like bash, hidden files
+// only match when the pattern itself starts with a literal dot.
+fn name_matches(pattern: &str, name: &str) -> bool {
+    if name.starts_with('.') && !pattern.starts_with('.') && !pattern.starts_with("\\.") {
+        return false;
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    match_from(&pattern, &name)
+}
+
+fn match_from(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => {
+            // Try every possible length for the star, shortest first
+            (0..=name.len()).any(|skip| match_from(&pattern[1..], &name[skip..]))
+        }
+        Some('?') => !name.is_empty() && match_from(&pattern[1..], &name[1..]),
+        Some('[') => match match_class(&pattern[1..], name.first().copied()) {
+            Some((true, rest)) => match_from(rest, &name[1..]),
+            Some((false, _)) => false,
+            // An unterminated `[` is an ordinary character
+            None => name.first() == Some(&'[') && match_from(&pattern[1..], &name[1..]),
+        },
+        Some('\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && match_from(&pattern[2..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && match_from(&pattern[1..], &name[1..]),
+    }
+}
+
+// Match a character against a `[...]` class whose opening bracket has been
+// consumed. Returns whether it matched and the pattern after the class, or
+// `None` when the class is not terminated.
+fn match_class(pattern: &[char], c: Option<char>) -> Option<(bool, &[char])> {
+    let mut i = 0;
+    let negated = matches!(pattern.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        let mut start = pattern[i];
+        // A `]` right after the opening bracket is part of the class
+        if start == ']' && !first {
+            return Some((c.is_some() && matched != negated, &pattern[i + 1..]));
+        }
+        if start == '\\' && i + 1 < pattern.len() {
+            i += 1;
+            start = pattern[i];
+        }
+        first = false;
+
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            let mut end = pattern[i + 2];
+            i += 2;
+            if end == '\\' && i + 1 < pattern.len() {
+                i += 1;
+                end = pattern[i];
+            }
+            matched |= c.is_some_and(|c| start <= c && c <= end);
+        } else {
+            matched |= c == Some(start);
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negated_class() {
+        assert!(name_matches("[!a]", "b"));
+        assert!(!name_matches("[!a]", "a"));
+        assert!(name_matches("[^a]x", "bx"));
+        assert!(!name_matches("[!a]", ""));
+    }
+
+    #[test]
+    fn bracket_first_in_class() {
+        assert!(name_matches("[]a]", "]"));
+        assert!(name_matches("[]a]", "a"));
+        assert!(!name_matches("[]a]", "b"));
+        assert!(name_matches("[!]]", "a"));
+        assert!(!name_matches("[!]]", "]"));
+    }
+
+    #[test]
+    fn trailing_dash_in_class() {
+        assert!(name_matches("[a-]", "a"));
+        assert!(name_matches("[a-]", "-"));
+        assert!(!name_matches("[a-]", "b"));
+        assert!(name_matches("[a-c]", "b"));
+        assert!(!name_matches("[a-c]", "d"));
+    }
+
+    #[test]
+    fn unterminated_class() {
+        assert!(name_matches("[ab", "[ab"));
+        assert!(!name_matches("[ab", "a"));
+        assert!(name_matches("x[*", "x[yz"));
+    }
+
+    #[test]
+    fn escaped_wildcards() {
+        // Quoted parts of a word arrive escaped
+        assert!(name_matches("\\*", "*"));
+        assert!(!name_matches("\\*", "a"));
+        assert!(name_matches("a\\?*", "a?b"));
+        assert!(!name_matches("a\\?*", "ab"));
+        assert!(name_matches("[\\]]", "]"));
+        assert!(!has_special("\\*\\?\\["));
+        assert_eq!(unescape("\\*a\\\\"), "*a\\");
+    }
+
+    #[test]
+    fn hidden_files() {
+        assert!(!name_matches("*", ".profile"));
+        assert!(!name_matches("?profile", ".profile"));
+        assert!(name_matches(".*", ".profile"));
+        assert!(name_matches("\\.*", ".profile"));
+        assert!(!name_matches(".*", "profile"));
+        assert!(name_matches("*", "profile"));
+    }
+}
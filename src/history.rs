@@ -0,0 +1,186 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+// Name of the history file, in the home directory
+const HISTORY_FILE: &str = ".0shell_history";
+// Number of lines kept across sessions
+const HISTORY_SIZE: usize = 1000;
+
+// Command lines typed by the user, shared by the line editor, the `history`
+// builtin and `!` recall, and saved to `~/.0shell_history`
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    // Load the lines saved by previous sessions
+    pub fn load() -> History {
+        let path = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+        let mut entries: Vec<String> = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| content.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        // Trim the file once it grows past the limit
+        if entries.len() > HISTORY_SIZE {
+            entries.drain(..entries.len() - HISTORY_SIZE);
+            if let Some(path) = &path {
+                let _ = fs::write(path, entries.join("\n") + "\n");
+            }
+        }
+
+        History { entries, path }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    // Record a line, skipping blank lines and repeats of the previous one.
+    // The line is appended to the file right away so it survives a crash.
+    pub fn add(&mut self, line: &str) {
+        if line.trim().is_empty() || self.entries.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+
+        if let Some(path) = &self.path {
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}", line);
+            }
+        }
+    }
+
+    // Replace `!!`, `!n`, `!-n` and `!prefix` with the matching history line.
+    // Returns `None` when the line has no event designator.
+    pub fn expand(&self, line: &str) -> Result<Option<String>, String> {
+        let chars: Vec<char> = line.chars().collect();
+        let mut result = String::new();
+        let mut expanded = false;
+        let mut in_single_quotes = false;
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            if c == '\'' {
+                in_single_quotes = !in_single_quotes;
+            }
+            // A `!` followed by a blank, `=`, a quote or nothing stays as is
+            let designator = c == '!'
+                && !in_single_quotes
+                && chars
+                    .get(i + 1)
+                    .is_some_and(|&next| next == '!' || !(is_separator(next) || next == '='));
+            if !designator {
+                result.push(c);
+                i += 1;
+                continue;
+            }
+
+            let start = i + 1;
+            let end = if chars[start] == '!' {
+                start + 1
+            } else {
+                let mut end = start;
+                if chars[end] == '-' {
+                    end += 1;
+                }
+                while end < chars.len() && !is_separator(chars[end]) {
+                    end += 1;
+                }
+                end
+            };
+            let event: String = chars[start..end].iter().collect();
+            match self.event(&event) {
+                Some(entry) => result.push_str(entry),
+                None => return Err(format!("!{}: event not found", event)),
+            }
+            expanded = true;
+            i = end;
+        }
+
+        Ok(expanded.then_some(result))
+    }
+
+    // Find the history line an event designator refers to
+    fn event(&self, event: &str) -> Option<&String> {
+        if event == "!" {
+            return self.entries.last();
+        }
+        if let Ok(n) = event.parse::<isize>() {
+            let index = if n < 0 {
+                self.entries.len().checked_sub(n.unsigned_abs())?
+            } else {
+                (n as usize).checked_sub(1)?
+            };
+            return self.entries.get(index);
+        }
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.starts_with(event))
+    }
+}
+
+// Characters that end a `!prefix` designator
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, ';' | '|' | '&' | '<' | '>' | '"' | '\'')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> History {
+        History {
+            entries: vec![
+                "ls -l".to_string(),
+                "echo hi".to_string(),
+                "cat f".to_string(),
+            ],
+            path: None,
+        }
+    }
+
+    fn expand(line: &str) -> Option<String> {
+        history().expand(line).unwrap()
+    }
+
+    #[test]
+    fn designators() {
+        assert_eq!(expand("!!").unwrap(), "cat f");
+        assert_eq!(expand("!! | wc").unwrap(), "cat f | wc");
+        assert_eq!(expand("!1").unwrap(), "ls -l");
+        assert_eq!(expand("!-2").unwrap(), "echo hi");
+        assert_eq!(expand("!ec;!l").unwrap(), "echo hi;ls -l");
+        assert_eq!(expand("echo \"!!\"").unwrap(), "echo \"cat f\"");
+    }
+
+    #[test]
+    fn left_as_is() {
+        assert_eq!(expand("ls"), None);
+        assert_eq!(expand("echo '!!' '!1'"), None);
+        assert_eq!(expand("echo ! a != b !"), None);
+        assert_eq!(expand("echo '!!'!!").unwrap(), "echo '!!'cat f");
+    }
+
+    #[test]
+    fn event_not_found() {
+        let error = |line| history().expand(line).unwrap_err();
+        assert_eq!(error("!4"), "!4: event not found");
+        assert_eq!(error("!0"), "!0: event not found");
+        assert_eq!(error("!-4"), "!-4: event not found");
+        assert_eq!(error("echo !x"), "!x: event not found");
+        let empty = History {
+            entries: Vec::new(),
+            path: None,
+        };
+        assert_eq!(empty.expand("!!").unwrap_err(), "!!: event not found");
+    }
+}
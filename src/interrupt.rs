@@ -0,0 +1,41 @@
+use std::{
+    io, mem, ptr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+// Set when Ctrl-C is pressed while a builtin runs inside the shell
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// Catch Ctrl-C instead of dying or ignoring it. Without SA_RESTART a builtin
+// blocked in a read gets EINTR, and exec still resets a caught signal to its
+// default in the programs the shell starts.
+pub fn install() {
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, ptr::null_mut());
+    }
+}
+
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+pub fn is_set() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// For long-running builtins to stop at the next convenient point. The error
+// is not of kind `Interrupted`, which `io::copy` and `write_all` would retry.
+pub fn check() -> io::Result<()> {
+    if is_set() {
+        return Err(io::Error::other("interrupted"));
+    }
+    Ok(())
+}
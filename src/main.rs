@@ -1,14 +1,31 @@
 mod commands;
-use commands::{cat, cd, cp, echo, ls, mkdir, mv, pwd, rm};
-use std::{
-    env,
-    io::{self, Write},
-};
+mod completion;
+mod editor;
+mod executor;
+mod expand;
+mod external;
+mod glob;
+mod history;
+mod interrupt;
+mod parser;
+mod redirect;
+
+use executor::{Flow, Shell};
+use std::env;
 
 const RESET: &str = "\x1b[0m";
 const CYAN: &str = "\x1b[36m";
 
 fn main() {
+    // Ctrl-C and Ctrl-\ are meant for the running program, not the shell.
+    // Ctrl-C still stops builtins, which run inside the shell.
+    unsafe {
+        libc::signal(libc::SIGQUIT, libc::SIG_IGN);
+    }
+    interrupt::install();
+
+    let mut shell = Shell::new();
+
     loop {
         let path = match env::current_dir() {
             Ok(path) => path.to_string_lossy().to_string(),
@@ -18,89 +35,45 @@ fn main() {
             }
         };
 
-        print!("{}{}{}$ ", CYAN, path, RESET);
-        std::io::stdout().flush().unwrap();
-
         // wait for a command
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_err() || input.is_empty() {
-            break;
-        }
-
-        let trimmed = input.trim();
-
-        let parts = get_parts(trimmed.to_string());
-        let (command, args) = parts;
-
-        match command.as_str() {
-            "" => continue,
-            "exit" => break,
-            "echo" => echo(args),
-            "cd" => cd(args),
-            "ls" => ls(args),
-            "pwd" => pwd(),
-            "cat" => cat(args),
-            "cp" => cp(args),
-            "rm" => rm(args),
-            "mv" => mv(args),
-            "mkdir" => mkdir(args),
-            _ => println!("Command '{}' not found.", command),
-        }
-    }
-}
-
-fn get_parts(input: String) -> (String, Vec<String>) {
-    let mut parts = Vec::new();
-    let mut current = String::new();
-    let mut in_double_quotes = false;
-    let mut in_single_quotes = false;
-
-    for c in input.chars() {
-        match c {
-            '"' => {
-                if !in_single_quotes {
-                    in_double_quotes = !in_double_quotes;
-                    if !in_double_quotes && !current.is_empty() {
-                        parts.push(current.clone());
-                        current.clear();
-                    }
-                } else {
-                    current.push(c);
-                }
-            }
-            '\'' => {
-                if !in_double_quotes {
-                    in_single_quotes = !in_single_quotes;
-                    if !in_single_quotes && !current.is_empty() {
-                        parts.push(current.clone());
-                        current.clear();
-                    }
-                } else {
-                    current.push(c);
-                }
+        let prompt = format!("{}{}{}$ ", CYAN, path, RESET);
+        let mut input = match editor::read_line(&prompt, shell.history.entries()) {
+            Ok(Some(input)) => input,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("0-shell: {}", e);
+                break;
             }
-            ' ' if !in_double_quotes && !in_single_quotes => {
-                if !current.is_empty() {
-                    parts.push(current.clone());
-                    current.clear();
-                }
+        };
+
+        // Recall `!!` and `!n`, showing the line that will run
+        match shell.history.expand(&input) {
+            Ok(Some(expanded)) => {
+                println!("{}", expanded);
+                input = expanded;
             }
-            _ => {
-                current.push(c);
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("0-shell: {}", e);
+                shell.status = 1;
+                continue;
             }
         }
-    }
+        shell.history.add(&input);
 
-    if !current.is_empty() {
-        parts.push(current);
-    }
+        let list = match parser::parse(input.trim()) {
+            Ok(list) => list,
+            Err(e) => {
+                eprintln!("0-shell: {}", e);
+                shell.status = 2;
+                continue;
+            }
+        };
 
-    if let Some((command, args)) = parts.split_first() {
-        (
-            command.to_string(),
-            args.iter().map(|s| s.to_string()).collect(),
-        )
-    } else {
-        ("".to_string(), vec![])
+        if let Flow::Exit(code) = shell.execute(&list) {
+            std::process::exit(code);
+        }
     }
+
+    std::process::exit(shell.status);
 }
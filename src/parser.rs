@@ -0,0 +1,397 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::os::unix::io::RawFd;
+use std::str::Chars;
+
+// A single command with its arguments and redirections. The parser produces
+// `Word`s, which become plain strings once expanded right before running.
+#[derive(Debug, Clone)]
+pub struct Command<W = Word> {
+    pub words: Vec<W>,
+    pub redirects: Vec<Redirect<W>>,
+}
+
+// Commands connected with `|`, each stdout feeding the next stdin
+pub type Pipeline = Vec<Command>;
+
+// Pipelines separated by `;`, `&&` or `||`. The connector of each pipeline
+// says how it depends on the previous one; the first is always `Sequence`.
+pub type List = Vec<(Connector, Pipeline)>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Connector {
+    // `;`: run unconditionally
+    Sequence,
+    // `&&`: run only if the previous pipeline succeeded
+    And,
+    // `||`: run only if the previous pipeline failed
+    Or,
+}
+
+// Redirections, applied from left to right like in POSIX shells
+#[derive(Debug, Clone)]
+pub enum Redirect<W = Word> {
+    // `< file`
+    Input(W),
+    // `> file`, `>> file`, `2> file`, `2>> file`
+    Output { fd: RawFd, path: W, append: bool },
+    // `2>&1`, `>&2`
+    Duplicate { fd: RawFd, to: RawFd },
+}
+
+// A word as typed, split where its quoting changes so that expansion knows
+// which parts are literal
+#[derive(Debug, Clone, Default)]
+pub struct Word(pub Vec<WordPart>);
+
+#[derive(Debug, Clone)]
+pub enum WordPart {
+    Unquoted(String),
+    SingleQuoted(String),
+    DoubleQuoted(String),
+}
+
+// Show a word the way it was typed, for error messages
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for part in &self.0 {
+            match part {
+                WordPart::Unquoted(text) => write!(f, "{}", text)?,
+                WordPart::SingleQuoted(text) => write!(f, "'{}'", text)?,
+                WordPart::DoubleQuoted(text) => write!(f, "\"{}\"", text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Quote {
+    None,
+    Single,
+    Double,
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(Word),
+    Pipe,
+    Separator(Connector),
+    Less,
+    Greater { fd: RawFd, append: bool },
+    Duplicate { fd: RawFd, to: RawFd },
+}
+
+pub fn parse(input: &str) -> Result<List, String> {
+    let mut tokens = tokenize(input)?.into_iter();
+    let mut list = Vec::new();
+    let mut connector = Connector::Sequence;
+    let mut pipeline = Vec::new();
+    let mut words: Vec<Word> = Vec::new();
+    let mut redirects = Vec::new();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => words.push(word),
+            Token::Pipe | Token::Separator(_) => {
+                if words.is_empty() {
+                    return Err(unexpected(token.describe()));
+                }
+                pipeline.push(to_command(
+                    std::mem::take(&mut words),
+                    std::mem::take(&mut redirects),
+                ));
+                if let Token::Separator(next) = token {
+                    list.push((connector, std::mem::take(&mut pipeline)));
+                    connector = next;
+                }
+            }
+            Token::Less => redirects.push(Redirect::Input(target(tokens.next())?)),
+            Token::Greater { fd, append } => redirects.push(Redirect::Output {
+                fd,
+                path: target(tokens.next())?,
+                append,
+            }),
+            Token::Duplicate { fd, to } => redirects.push(Redirect::Duplicate { fd, to }),
+        }
+    }
+
+    if words.is_empty() {
+        // A trailing `|`, `&&` or `||` has nothing to run after it, and
+        // redirections need a command; a trailing `;` is fine
+        if !pipeline.is_empty() || !redirects.is_empty() || connector != Connector::Sequence {
+            return Err(unexpected("newline"));
+        }
+    } else {
+        pipeline.push(to_command(words, redirects));
+        list.push((connector, pipeline));
+    }
+
+    Ok(list)
+}
+
+fn to_command(words: Vec<Word>, redirects: Vec<Redirect>) -> Command {
+    Command { words, redirects }
+}
+
+// A redirection operator must be followed by a file name
+fn target(token: Option<Token>) -> Result<Word, String> {
+    match token {
+        Some(Token::Word(word)) => Ok(word),
+        Some(token) => Err(unexpected(token.describe())),
+        None => Err(unexpected("newline")),
+    }
+}
+
+impl Token {
+    // How the token is shown in syntax errors
+    fn describe(&self) -> &'static str {
+        match self {
+            Token::Word(_) => "word",
+            Token::Pipe => "|",
+            Token::Separator(Connector::Sequence) => ";",
+            Token::Separator(Connector::And) => "&&",
+            Token::Separator(Connector::Or) => "||",
+            Token::Less => "<",
+            Token::Greater { append: false, .. } => ">",
+            Token::Greater { append: true, .. } => ">>",
+            Token::Duplicate { .. } => ">&",
+        }
+    }
+}
+
+fn unexpected(token: &str) -> String {
+    format!("syntax error near unexpected token `{}'", token)
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut word = Word::default();
+    let mut current = String::new();
+    let mut quote = Quote::None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('"', Quote::None) | ('\'', Quote::None) => {
+                push_part(&mut word, &mut current, quote);
+                quote = if c == '"' {
+                    Quote::Double
+                } else {
+                    Quote::Single
+                };
+            }
+            // Closing a quote keeps even an empty part, so that "" is an empty argument
+            ('"', Quote::Double) | ('\'', Quote::Single) => {
+                word.0.push(part(std::mem::take(&mut current), quote));
+                quote = Quote::None;
+            }
+            (_, Quote::Single) | (_, Quote::Double) => current.push(c),
+            (' ' | '\t', _) => push_word(&mut tokens, &mut word, &mut current),
+            ('|', _) => {
+                push_word(&mut tokens, &mut word, &mut current);
+                if chars.next_if_eq(&'|').is_some() {
+                    tokens.push(Token::Separator(Connector::Or));
+                } else {
+                    tokens.push(Token::Pipe);
+                }
+            }
+            ('&', _) => {
+                push_word(&mut tokens, &mut word, &mut current);
+                if chars.next_if_eq(&'&').is_none() {
+                    // Background jobs are not supported
+                    return Err(unexpected("&"));
+                }
+                tokens.push(Token::Separator(Connector::And));
+            }
+            (';', _) => {
+                push_word(&mut tokens, &mut word, &mut current);
+                tokens.push(Token::Separator(Connector::Sequence));
+            }
+            ('<', _) => {
+                push_word(&mut tokens, &mut word, &mut current);
+                tokens.push(Token::Less);
+            }
+            ('>', _) => {
+                push_word(&mut tokens, &mut word, &mut current);
+                tokens.push(redirect_output(libc::STDOUT_FILENO, &mut chars)?);
+            }
+            // A lone `1` or `2` right before `>` names the descriptor to redirect
+            ('1' | '2', _)
+                if word.0.is_empty() && current.is_empty() && chars.peek() == Some(&'>') =>
+            {
+                chars.next();
+                let fd = if c == '1' {
+                    libc::STDOUT_FILENO
+                } else {
+                    libc::STDERR_FILENO
+                };
+                tokens.push(redirect_output(fd, &mut chars)?);
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if quote != Quote::None {
+        return Err("unexpected EOF while looking for matching quote".to_string());
+    }
+    push_word(&mut tokens, &mut word, &mut current);
+
+    Ok(tokens)
+}
+
+// Read what follows a `>`: `>` for append or `&N` for a duplication
+fn redirect_output(fd: RawFd, chars: &mut Peekable<Chars>) -> Result<Token, String> {
+    match chars.peek() {
+        Some('>') => {
+            chars.next();
+            Ok(Token::Greater { fd, append: true })
+        }
+        Some('&') => {
+            chars.next();
+            match chars.next() {
+                Some('1') => Ok(Token::Duplicate {
+                    fd,
+                    to: libc::STDOUT_FILENO,
+                }),
+                Some('2') => Ok(Token::Duplicate {
+                    fd,
+                    to: libc::STDERR_FILENO,
+                }),
+                Some(c) => Err(unexpected(&c.to_string())),
+                None => Err(unexpected("newline")),
+            }
+        }
+        _ => Ok(Token::Greater { fd, append: false }),
+    }
+}
+
+fn part(text: String, quote: Quote) -> WordPart {
+    match quote {
+        Quote::None => WordPart::Unquoted(text),
+        Quote::Single => WordPart::SingleQuoted(text),
+        Quote::Double => WordPart::DoubleQuoted(text),
+    }
+}
+
+fn push_part(word: &mut Word, current: &mut String, quote: Quote) {
+    if !current.is_empty() {
+        word.0.push(part(std::mem::take(current), quote));
+    }
+}
+
+fn push_word(tokens: &mut Vec<Token>, word: &mut Word, current: &mut String) {
+    push_part(word, current, Quote::None);
+    if !word.0.is_empty() {
+        tokens.push(Token::Word(std::mem::take(word)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A parsed line written back compactly: each word in brackets, each
+    // redirection with its descriptor, and the operators in between
+    fn show(input: &str) -> String {
+        let mut shown = String::new();
+        for (i, (connector, pipeline)) in parse(input).unwrap().iter().enumerate() {
+            if i > 0 {
+                shown.push_str(match connector {
+                    Connector::Sequence => " ; ",
+                    Connector::And => " && ",
+                    Connector::Or => " || ",
+                });
+            }
+            let commands: Vec<String> = pipeline.iter().map(show_command).collect();
+            shown.push_str(&commands.join(" | "));
+        }
+        shown
+    }
+
+    fn show_command(command: &Command) -> String {
+        let mut items: Vec<String> = command
+            .words
+            .iter()
+            .map(|word| format!("[{}]", text(word)))
+            .collect();
+        for redirect in &command.redirects {
+            items.push(match redirect {
+                Redirect::Input(path) => format!("<[{}]", text(path)),
+                Redirect::Output { fd, path, append } => {
+                    let operator = if *append { ">>" } else { ">" };
+                    format!("{}{}[{}]", fd, operator, text(path))
+                }
+                Redirect::Duplicate { fd, to } => format!("{}>&{}", fd, to),
+            });
+        }
+        items.join(" ")
+    }
+
+    fn text(word: &Word) -> String {
+        word.0
+            .iter()
+            .map(|part| match part {
+                WordPart::Unquoted(text)
+                | WordPart::SingleQuoted(text)
+                | WordPart::DoubleQuoted(text) => text.as_str(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(show("echo 'a b'  \"c  d\"e"), "[echo] [a b] [c  de]");
+        assert_eq!(show("echo '|;&' \"<>\""), "[echo] [|;&] [<>]");
+        assert_eq!(show("echo \"it's\" '\"'"), "[echo] [it's] [\"]");
+    }
+
+    #[test]
+    fn empty_argument() {
+        assert_eq!(show("echo \"\" x ''"), "[echo] [] [x] []");
+        assert_eq!(show("echo a\"\"b"), "[echo] [ab]");
+    }
+
+    #[test]
+    fn redirections_keep_their_order() {
+        assert_eq!(show("ls 2>&1 > f"), "[ls] 2>&1 1>[f]");
+        assert_eq!(show("ls > f 2>&1"), "[ls] 1>[f] 2>&1");
+        assert_eq!(show("cat <in >>out 2>err"), "[cat] <[in] 1>>[out] 2>[err]");
+        assert_eq!(show("echo x >&2"), "[echo] [x] 1>&2");
+    }
+
+    #[test]
+    fn descriptor_digits() {
+        // Only a lone 1 or 2 names a descriptor
+        assert_eq!(show("echo 12>f"), "[echo] [12] 1>[f]");
+        assert_eq!(show("echo a2>f"), "[echo] [a2] 1>[f]");
+        assert_eq!(show("echo 2 >f"), "[echo] [2] 1>[f]");
+    }
+
+    #[test]
+    fn pipelines_and_lists() {
+        assert_eq!(show("ls -l | wc -l"), "[ls] [-l] | [wc] [-l]");
+        assert_eq!(show("a && b || c"), "[a] && [b] || [c]");
+        assert_eq!(show("a;b | c;"), "[a] ; [b] | [c]");
+        assert_eq!(show("a||b&&c"), "[a] || [b] && [c]");
+        assert!(parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn syntax_errors() {
+        let error = |input| parse(input).unwrap_err();
+        assert_eq!(error("| ls"), unexpected("|"));
+        assert_eq!(error("ls |"), unexpected("newline"));
+        assert_eq!(error("a && && b"), unexpected("&&"));
+        assert_eq!(error("a ||"), unexpected("newline"));
+        assert_eq!(error("; a"), unexpected(";"));
+        assert_eq!(error("sleep 1 &"), unexpected("&"));
+        assert_eq!(error("ls > | wc"), unexpected("|"));
+        assert_eq!(error("ls 2>&x"), unexpected("x"));
+        assert_eq!(error("> f"), unexpected("newline"));
+        assert_eq!(
+            error("echo 'a"),
+            "unexpected EOF while looking for matching quote"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use crate::interrupt;
+use crate::parser::Redirect;
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    mem::{self, ManuallyDrop},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
+};
+
+// Where a builtin reads its standard input from
+pub enum Input {
+    // The shell's own stdin, shared with the prompt
+    Stdin,
+    // A raw descriptor, used in forked children where the prompt buffer is stale
+    Fd(ManuallyDrop<File>),
+    File(File),
+}
+
+// Where a builtin writes its standard output or error
+pub enum Output {
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+// The three standard streams handed to a builtin
+pub struct Streams {
+    pub input: Input,
+    pub out: Output,
+    pub err: Output,
+}
+
+impl Input {
+    pub fn from_fd(fd: RawFd) -> Input {
+        Input::Fd(ManuallyDrop::new(unsafe { File::from_raw_fd(fd) }))
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let result = match self {
+            Input::Stdin => io::stdin().read(buf),
+            Input::Fd(file) => file.read(buf),
+            Input::File(file) => file.read(buf),
+        };
+        // A read cut short by Ctrl-C ends the input instead of being retried
+        if result
+            .as_ref()
+            .is_err_and(|e| e.kind() == io::ErrorKind::Interrupted)
+        {
+            interrupt::check()?;
+        }
+        result
+    }
+}
+
+impl Output {
+    // Number of columns of the terminal this output goes to, or `None` when
+    // it is not a terminal
+    pub fn terminal_width(&self) -> Option<usize> {
+        let fd = match self {
+            Output::Stdout => libc::STDOUT_FILENO,
+            Output::Stderr => libc::STDERR_FILENO,
+            Output::File(file) => file.as_raw_fd(),
+        };
+        if unsafe { libc::isatty(fd) } == 0 {
+            return None;
+        }
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            Some(size.ws_col as usize)
+        } else {
+            Some(80)
+        }
+    }
+
+    fn try_clone(&self) -> io::Result<Output> {
+        match self {
+            Output::Stdout => Ok(Output::Stdout),
+            Output::Stderr => Ok(Output::Stderr),
+            Output::File(file) => Ok(Output::File(file.try_clone()?)),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout => io::stdout().write(buf),
+            Output::Stderr => io::stderr().write(buf),
+            Output::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout => io::stdout().flush(),
+            Output::Stderr => io::stderr().flush(),
+            Output::File(file) => file.flush(),
+        }
+    }
+}
+
+impl Streams {
+    pub fn new(input: Input) -> Streams {
+        Streams {
+            input,
+            out: Output::Stdout,
+            err: Output::Stderr,
+        }
+    }
+
+    // Open the files of each redirection in order, so that `> f 2>&1` and
+    // `2>&1 > f` end up with different targets
+    pub fn redirect(mut self, redirects: &[Redirect<String>]) -> Result<Streams, String> {
+        for redirect in redirects {
+            match redirect {
+                Redirect::Input(path) => {
+                    let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+                    self.input = Input::File(file);
+                }
+                Redirect::Output { fd, path, append } => {
+                    let file =
+                        open_output(path, *append).map_err(|e| format!("{}: {}", path, e))?;
+                    *self.output(*fd) = Output::File(file);
+                }
+                Redirect::Duplicate { fd, to } => {
+                    let target = self
+                        .output(*to)
+                        .try_clone()
+                        .map_err(|e| format!("{}: {}", to, e))?;
+                    *self.output(*fd) = target;
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    fn output(&mut self, fd: RawFd) -> &mut Output {
+        if fd == libc::STDERR_FILENO {
+            &mut self.err
+        } else {
+            &mut self.out
+        }
+    }
+}
+
+fn open_output(path: &str, append: bool) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+}
+
+// Apply the redirections directly on the process descriptors, for a forked
+// child that is about to exec an external program
+pub fn apply_to_fds(redirects: &[Redirect<String>]) -> Result<(), String> {
+    for redirect in redirects {
+        let (fd, source) = match redirect {
+            Redirect::Input(path) => {
+                let file = File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+                (libc::STDIN_FILENO, file.into_raw_fd())
+            }
+            Redirect::Output { fd, path, append } => {
+                let file = open_output(path, *append).map_err(|e| format!("{}: {}", path, e))?;
+                (*fd, file.into_raw_fd())
+            }
+            Redirect::Duplicate { fd, to } => {
+                if unsafe { libc::dup2(*to, *fd) } < 0 {
+                    return Err(format!("{}: {}", to, io::Error::last_os_error()));
+                }
+                continue;
+            }
+        };
+        unsafe {
+            libc::dup2(source, fd);
+            libc::close(source);
+        }
+    }
+    Ok(())
+}